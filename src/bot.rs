@@ -1,59 +1,168 @@
 use serenity::futures::StreamExt;
 use serenity::{
     async_trait,
+    builder::CreateEmbed,
     cache::Cache,
     client::Context,
-    http::client::Http,
-    model::{channel::Message, channel::ReactionType, gateway::Ready, id::ChannelId},
+    http::{client::Http, StatusCode},
+    model::{
+        channel::ReactionType,
+        channel::{Embed, Message},
+        gateway::Ready,
+        id::{ChannelId, GuildId, UserId},
+    },
     prelude::*,
 };
 
-use crate::github_scraper;
-use crate::github_scraper::{DiscussionLink, DiscussionPost};
+use crate::config::{fill_template, BotConfig};
+use crate::discord_markup::{sanitize, sanitize_mentions};
+use crate::discussion_source::{self, DiscussionSource};
+use crate::github_scraper::{DiscussionPost, ScraperConfig};
+use crate::message_split::{is_continuation, split_message, MESSAGE_LIMIT};
+use crate::renderer::{DiscordRenderer, Renderer};
+use crate::state_store::{self, ForwardedRecord, StateStore};
+use chrono::{DateTime, Utc};
+use rand::Rng;
 use std::cmp::max;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-macro_rules! DELETED_MESSAGE_WARNING { () => { "I've deleted your message from the opportunities channel. It said: \n\n{}\n\nPlease post opportunities here: {}" }; }
+/// Discord's limits on the length of embed titles and descriptions, in characters.
+const EMBED_TITLE_LIMIT: usize = 256;
+const EMBED_DESCRIPTION_LIMIT: usize = 4096;
+const EMBED_FIELD_LIMIT: usize = 1024;
 
-struct Handler;
+/// One of the messages a post is forwarded as.
+enum ForwardedMessage {
+    Text(String),
+    Embed(CreateEmbed),
+}
+
+#[derive(Clone)]
+struct Handler {
+    config: Arc<BotConfig>,
+
+    /// Where opportunities are forwarded from.
+    sources: Vec<Arc<dyn DiscussionSource>>,
+
+    /// Which opportunities have been forwarded where.
+    store: Arc<dyn StateStore>,
+
+    /// True iff the background polling task has been started.
+    /// Reconnecting triggers `ready` again, but should not start another task.
+    polling_started: Arc<AtomicBool>,
+
+    /// Held while forwarding, so that a poll and a reconnect don't
+    /// both post the same opportunities.
+    forwarding: Arc<tokio::sync::Mutex<()>>,
+}
 
 impl Handler {
+    fn new(
+        config: BotConfig,
+        sources: Vec<Arc<dyn DiscussionSource>>,
+        store: Arc<dyn StateStore>,
+    ) -> Self {
+        Handler {
+            config: Arc::new(config),
+            sources,
+            store,
+            polling_started: Arc::new(AtomicBool::new(false)),
+            forwarding: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
     /// Delete an illegal message, `msg` and direct messages the author an appropriate
-    /// explanation.
+    /// explanation (if configured to).
     /// If unable to delete the message (an error!) no direct message is sent to the author.
     async fn block_illegal_post(
         &self,
         context: Context,
         msg: &Message,
     ) -> Result<(), SerenityError> {
-        let reply_text = format!(
-            DELETED_MESSAGE_WARNING!(),
-            msg.content,
-            github_scraper::OPPORTUNITIES_POST_TO_URL
+        let post_to_url = match self.sources.first() {
+            Some(source) => source.get_post_to_url(),
+            None => ScraperConfig::default().get_post_to_url(),
+        };
+        let reply_text = fill_template(
+            &self.config.templates.deleted_message,
+            &[("content", &msg.content), ("post_to_url", &post_to_url)],
         );
 
-        let deletion = msg.delete(context.http.clone()).await;
-        if let Err(why) = deletion {
-            return Err(why);
+        msg.delete(context.http.clone()).await?;
+
+        if !self.config.moderation.dm_author {
+            return Ok(());
         }
 
-        let reply = msg
-            .author
+        msg.author
             .dm(&context, |m| {
                 m.content(reply_text);
                 m
             })
-            .await;
-        if let Err(why) = reply {
-            return Err(why);
-        }
+            .await?;
 
         Ok(())
     }
 
+    /// Returns true iff the author of `msg`, posted in the guild `guild_id`, may post in
+    /// target channels, because they or one of their roles are exempt from moderation.
+    /// Authors' roles are looked up at most once, and remembered in `known_roles`.
+    async fn is_exempt(
+        &self,
+        context: &Context,
+        guild_id: Option<GuildId>,
+        msg: &Message,
+        known_roles: &mut HashMap<UserId, Vec<u64>>,
+    ) -> Result<bool, SerenityError> {
+        let moderation = &self.config.moderation;
+        let user_id = msg.author.id;
+        if moderation.is_exempt(user_id.0, &[]) {
+            return Ok(true);
+        }
+        if moderation.exempt_roles.is_empty() {
+            return Ok(false);
+        }
+
+        // New messages include the author's roles, but those from a channel's history don't.
+        if let Some(member) = &msg.member {
+            let roles: Vec<u64> = member.roles.iter().map(|role| role.0).collect();
+            return Ok(moderation.is_exempt(user_id.0, &roles));
+        }
+
+        let guild_id = match guild_id {
+            Some(guild_id) => guild_id,
+            None => return Ok(false),
+        };
+        if let Some(roles) = known_roles.get(&user_id) {
+            return Ok(moderation.is_exempt(user_id.0, roles));
+        }
+
+        let roles = match guild_id.member(context, user_id).await {
+            Ok(member) => member.roles.iter().map(|role| role.0).collect(),
+
+            // Authors who have left the guild have no roles.
+            Err(SerenityError::Http(error))
+                if error.status_code() == Some(StatusCode::NOT_FOUND) =>
+            {
+                Vec::new()
+            }
+            Err(why) => return Err(why),
+        };
+        let exempt = moderation.is_exempt(user_id.0, &roles);
+        known_roles.insert(user_id, roles);
+
+        Ok(exempt)
+    }
+
     /// Returns whether a channel with the given name applies to this.
     fn is_target_channel(&self, channel_name: &Option<String>) -> bool {
-        channel_name == &Some("opportunities".to_string())
+        match channel_name {
+            Some(name) => self.config.discord.channels.contains(name),
+            None => false,
+        }
     }
 
     /// Get a list of all channels we should manage.
@@ -84,7 +193,8 @@ impl Handler {
     }
 
     /// Delete all illegal posts from `channel`. A message is considered illegal if
-    /// it was posted after the bot's last post in `channel`.
+    /// it was posted after the bot's last post in `channel` by someone not exempt
+    /// from moderation.
     async fn delete_illegal_posts(
         &self,
         context: Context,
@@ -93,6 +203,18 @@ impl Handler {
         let mut found_own = false;
         let mut target_posts: Vec<Box<Arc<Message>>> = Vec::new();
 
+        // Needed to look up the roles of the posts' authors.
+        let guild_id = if self.config.moderation.exempt_roles.is_empty() {
+            None
+        } else {
+            channel
+                .to_channel(&context)
+                .await?
+                .guild()
+                .map(|channel| channel.guild_id)
+        };
+        let mut known_roles = HashMap::new();
+
         // See documentation for ChannelId::messages_iter.
         let mut messages_stream = channel.messages_iter(&context).boxed();
         while let Some(message) = messages_stream.next().await {
@@ -105,7 +227,16 @@ impl Handler {
                 break;
             }
 
-            target_posts.push(Box::new(Arc::new(message)));
+            let exempt = self
+                .is_exempt(&context, guild_id, &message, &mut known_roles)
+                .await;
+            match exempt {
+                Ok(false) => target_posts.push(Box::new(Arc::new(message))),
+                Ok(true) => {}
+
+                // Rather delete nothing than a moderator's post.
+                Err(why) => println!("Error checking whether a post is exempt! {:?}", why),
+            }
         }
 
         if found_own {
@@ -117,23 +248,21 @@ impl Handler {
         Ok(())
     }
 
+    /// Get the id of the most recent opportunity from `source` that we forwarded to `channel`,
+    /// or zero, if none have been forwarded.
     async fn get_last_posted_opportunity_id(
         &self,
         context: Context,
         channel: &ChannelId,
-    ) -> Result<u16, SerenityError> {
-        let mut most_recent_id: u16 = 0;
+        source: &dyn DiscussionSource,
+    ) -> Result<u64, SerenityError> {
+        let mut most_recent_id: u64 = 0;
 
         let mut messages_stream = channel.messages_iter(&context).boxed();
         while let Some(message) = messages_stream.next().await {
             let message = message?;
             if message.is_own(&context).await {
-                // Our messages should contain a link to the opportunity.
-                // Such links are of the form:
-                //    https://.../.../.../discussions/integer
-                // We want to extract the integer.
-                if let Some(link) = DiscussionLink::pull_from(&message.content).get(0) {
-                    let id = link.get_id();
+                if let Some(id) = get_forwarded_id(source, &message.content, &message.embeds) {
                     most_recent_id = max(id, most_recent_id);
 
                     // Newer posts have greater ids. As we iterate from most recent to least recent
@@ -153,46 +282,356 @@ impl Handler {
         context: Context,
         channel: &ChannelId,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // Find the most recent post (by us) and extract its index.
-        let last_posted_id = self
-            .get_last_posted_opportunity_id(context.clone(), channel)
-            .await?;
+        for source in self.sources.iter() {
+            // A failing source shouldn't stop the others from being forwarded.
+            if let Err(why) = self
+                .forward_opportunities_from(context.clone(), channel, source.as_ref())
+                .await
+            {
+                println!(
+                    "Error forwarding opportunities from {}: {:?}",
+                    source.get_source_key(),
+                    why
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forward new opportunities from `source` to `channel`.
+    async fn forward_opportunities_from(
+        &self,
+        context: Context,
+        channel: &ChannelId,
+        source: &dyn DiscussionSource,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let source_key = source.get_source_key();
+        if self
+            .store
+            .get_last_forwarded_id(channel.0, &source_key)?
+            .is_none()
+        {
+            // Nothing has been recorded for this channel. We may have forwarded
+            // opportunities before we started recording them, so find the most
+            // recent post (by us) and extract its index.
+            let id = self
+                .get_last_posted_opportunity_id(context.clone(), channel, source)
+                .await?;
+
+            if id > 0 {
+                self.store.record_forwarded(ForwardedRecord {
+                    channel_id: channel.0,
+                    source: source_key.clone(),
+                    discussion_id: id,
+                    message_ids: Vec::new(),
+                    edited_at: None,
+                })?;
+            }
+        }
 
         // Forward all newer opportunities.
-        let discussion_links = DiscussionLink::fetch().await?;
-        let newer_opportunities = discussion_links
-            .iter()
-            .filter(|link| link.get_id() > last_posted_id)
-            .map(|link| DiscussionPost::fetch_from(link.clone()));
-
-        for promise in newer_opportunities {
-            let post = promise.await?;
-            let url = post.get_link().get_url();
-            let author = post.get_author();
-            let content = post.get_content();
+        let posts = self.get_unforwarded_posts(channel.0, source).await?;
+        for post in posts {
+            let mut message_ids = Vec::new();
+            let result = self
+                .send_post(context.clone(), channel, &post, &mut message_ids)
+                .await;
+
+            // Remember even partially-sent posts, so that they aren't sent twice.
+            if !message_ids.is_empty() {
+                self.store.record_forwarded(ForwardedRecord {
+                    channel_id: channel.0,
+                    source: source_key.clone(),
+                    discussion_id: post.get_link().get_id(),
+                    message_ids,
+                    edited_at: get_edit_time(&post),
+                })?;
+            }
+
+            if let Err(why) = result {
+                // Later posts would move the cursor past this one, so stop here
+                // and try again next time.
+                println!("Error forwarding {}: {:?}", post.get_link().get_url(), why);
+                break;
+            }
+        }
+
+        self.update_edited_posts(context, channel, source).await
+    }
+
+    /// Update the messages of discussions from `source` that were recently forwarded
+    /// to `channel`, and have been edited since.
+    async fn update_edited_posts(
+        &self,
+        context: Context,
+        channel: &ChannelId,
+        source: &dyn DiscussionSource,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let edited = self.get_edited_posts(channel.0, source).await?;
+        for (record, post) in edited {
+            let mut message_ids = Vec::new();
+            let result = self
+                .update_post(context.clone(), channel, &record, &post, &mut message_ids)
+                .await;
+
+            // Remember what was sent, even if the update failed partway.
+            if !message_ids.is_empty() {
+                self.store.record_forwarded(ForwardedRecord {
+                    message_ids,
+                    edited_at: get_edit_time(&post),
+                    ..record
+                })?;
+            }
+
+            if let Err(why) = result {
+                println!("Error updating {}: {:?}", post.get_link().get_url(), why);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replace the messages `record` says `post` was forwarded as, adding the ids of
+    /// the new messages to `message_ids`. The messages are edited if `post` still
+    /// needs as many, and are otherwise deleted and sent again.
+    async fn update_post(
+        &self,
+        context: Context,
+        channel: &ChannelId,
+        record: &ForwardedRecord,
+        post: &DiscussionPost,
+        message_ids: &mut Vec<u64>,
+    ) -> Result<(), serenity::Error> {
+        let messages = self.get_forwarded_messages(post);
+
+        if messages.len() != record.message_ids.len() {
+            for &message_id in record.message_ids.iter() {
+                match channel.delete_message(&context, message_id).await {
+                    // Someone else already deleted it.
+                    Err(SerenityError::Http(error))
+                        if error.status_code() == Some(StatusCode::NOT_FOUND) => {}
+                    result => result?,
+                }
+            }
+
+            return self.send_post(context, channel, post, message_ids).await;
+        }
 
+        for (&message_id, forwarded) in record.message_ids.iter().zip(messages) {
             channel
+                .edit_message(&context, message_id, |m| match forwarded {
+                    ForwardedMessage::Text(text) => m.content(text),
+                    ForwardedMessage::Embed(embed) => m.content("").set_embed(embed),
+                })
+                .await?;
+
+            message_ids.push(message_id);
+        }
+
+        Ok(())
+    }
+
+    /// Send `post` to `channel`, adding the ids of the messages sent to `message_ids`.
+    async fn send_post(
+        &self,
+        context: Context,
+        channel: &ChannelId,
+        post: &DiscussionPost,
+        message_ids: &mut Vec<u64>,
+    ) -> Result<(), serenity::Error> {
+        for forwarded in self.get_forwarded_messages(post) {
+            let message = channel
                 .send_message(&context, |m| {
-                    m.content(format!(
-                        "**Forwarded message from** {}:\n**Author:** {}\n\n{}",
-                        url, author, content
-                    ));
+                    match forwarded {
+                        ForwardedMessage::Text(text) => m.content(text),
+                        ForwardedMessage::Embed(embed) => m.set_embed(embed),
+                    };
+
+                    // Forwarded content comes from GitHub, and should never ping anyone.
+                    m.allowed_mentions(|am| am.empty_parse());
 
                     m
                 })
                 .await?;
+
+            message_ids.push(message.id.0);
         }
 
         Ok(())
     }
 
+    /// Get the messages `post` is forwarded as. Long posts are split across
+    /// several messages.
+    fn get_forwarded_messages(&self, post: &DiscussionPost) -> Vec<ForwardedMessage> {
+        if !self.config.embeds.enabled {
+            return split_message(&self.get_forwarded_text(post), MESSAGE_LIMIT)
+                .into_iter()
+                .map(ForwardedMessage::Text)
+                .collect();
+        }
+
+        let color = self.config.embeds.get_color(post.get_category());
+        let content = sanitize_mentions(post.get_content());
+        split_message(&content, EMBED_DESCRIPTION_LIMIT)
+            .into_iter()
+            .enumerate()
+            .map(|(index, description)| {
+                let mut embed = CreateEmbed::default();
+                if index == 0 {
+                    self.fill_embed(&mut embed, post, &description);
+                } else {
+                    embed.description(description).colour(color);
+                }
+
+                ForwardedMessage::Embed(embed)
+            })
+            .collect()
+    }
+
+    /// Get the plain-text message `post` is forwarded as.
+    fn get_forwarded_text(&self, post: &DiscussionPost) -> String {
+        let url = post.get_link().get_url();
+        let posted = match post.get_created_at() {
+            Some(time) => discord_timestamp(time),
+            None => "at an unknown time".to_string(),
+        };
+        let edited = match post.get_edited_at() {
+            Some(time) if post.is_edited() => format!("edited {}", discord_timestamp(time)),
+            _ => String::new(),
+        };
+
+        fill_template(
+            &self.config.templates.forwarded_message,
+            &[
+                ("title", &sanitize_mentions(&escape_title(&get_title(post)))),
+                ("posted", &posted),
+                ("edited", &edited),
+                ("url", &url),
+                ("author", post.get_author()),
+                ("content", &sanitize_mentions(post.get_content())),
+            ],
+        )
+    }
+
+    /// Describe `post` with `embed`, using `description` in place of its content.
+    fn fill_embed(&self, embed: &mut CreateEmbed, post: &DiscussionPost, description: &str) {
+        embed
+            .title(truncate(&sanitize(&get_title(post)), EMBED_TITLE_LIMIT))
+            .url(post.get_link().get_url())
+            .description(description)
+            .colour(self.config.embeds.get_color(post.get_category()));
+
+        embed.author(|author| {
+            author.name(post.get_author());
+            if let Some(avatar) = post.get_author_avatar_url() {
+                author.icon_url(avatar);
+            }
+            author
+        });
+
+        if !post.get_labels().is_empty() {
+            embed.field("Labels", sanitize(&post.get_labels().join(", ")), true);
+        }
+
+        // Embeds can show only one image. List the rest.
+        if let Some((first, rest)) = post.get_images().split_first() {
+            embed.image(first);
+
+            let mut others = String::new();
+            for (index, url) in rest.iter().enumerate() {
+                let link = format!("[Image {}]({})\n", index + 2, url);
+                if others.chars().count() + link.chars().count() > EMBED_FIELD_LIMIT {
+                    break;
+                }
+                others.push_str(&link);
+            }
+            if !others.is_empty() {
+                embed.field("More images", others, false);
+            }
+        }
+
+        let footer = match post.get_edited_at() {
+            Some(time) if post.is_edited() => format!(
+                "Discussion #{} · edited {}",
+                post.get_link().get_id(),
+                time.format("%b %-d, %Y")
+            ),
+            _ => format!("Discussion #{}", post.get_link().get_id()),
+        };
+        embed.footer(|f| f.text(footer));
+
+        if let Some(created_at) = post.get_created_at() {
+            embed.timestamp(&created_at);
+        }
+    }
+
+    /// Fetch the posts from `source` newer than the last one forwarded to the channel
+    /// with id `channel_id` that haven't been forwarded there.
+    async fn get_unforwarded_posts(
+        &self,
+        channel_id: u64,
+        source: &dyn DiscussionSource,
+    ) -> Result<Vec<DiscussionPost>, Box<dyn std::error::Error>> {
+        let source_key = source.get_source_key();
+        let cursor = self.store.get_last_forwarded_id(channel_id, &source_key)?;
+
+        let links = source.list_since(cursor).await?;
+        let mut posts = Vec::new();
+        for link in links {
+            if !self
+                .store
+                .is_forwarded(channel_id, &source_key, link.get_id())?
+            {
+                posts.push(source.fetch(&link).await?);
+            }
+        }
+
+        // Forward in id order, like the cursor. Otherwise, if forwarding stopped
+        // partway, posts with lower ids than one already forwarded would be skipped.
+        posts.sort_by_key(|post| post.get_link().get_id());
+
+        Ok(posts)
+    }
+
+    /// Fetch the discussions from `source` most recently forwarded to the channel with
+    /// id `channel_id` that have been edited since. Each is returned with its record.
+    async fn get_edited_posts(
+        &self,
+        channel_id: u64,
+        source: &dyn DiscussionSource,
+    ) -> Result<Vec<(ForwardedRecord, DiscussionPost)>, Box<dyn std::error::Error>> {
+        let records = self.store.get_recent_records(
+            channel_id,
+            &source.get_source_key(),
+            self.config.polling.edit_check_count,
+        )?;
+
+        let mut edited = Vec::new();
+        for record in records {
+            // Messages recovered from the channel's history can't be updated.
+            if record.message_ids.is_empty() {
+                continue;
+            }
+
+            let post = source.fetch(&source.get_link(record.discussion_id)).await?;
+            if get_edit_time(&post) > record.edited_at {
+                edited.push((record, post));
+            }
+        }
+
+        Ok(edited)
+    }
+
     async fn handle_channel(
         &self,
         context: Context,
         channel_id: &ChannelId,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if let Err(why) = self.delete_illegal_posts(context.clone(), channel_id).await {
-            return Err(Box::new(why));
+        if self.config.moderation.delete_user_posts {
+            if let Err(why) = self.delete_illegal_posts(context.clone(), channel_id).await {
+                return Err(Box::new(why));
+            }
         }
 
         self.forward_opportunities(context.clone(), channel_id)
@@ -200,6 +639,121 @@ impl Handler {
 
         Ok(())
     }
+
+    /// Run [Handler::handle_channel] (if `moderate`) or [Handler::forward_opportunities]
+    /// for every target channel. Errors are logged, rather than returned, so that one
+    /// failing channel doesn't prevent forwarding to the others.
+    async fn handle_all_channels(&self, context: Context, moderate: bool) {
+        // Wait for any other forwarding to finish, so we see the messages it posted.
+        let _forwarding = self.forwarding.lock().await;
+
+        let channels = match self.get_target_channels(context.clone()).await {
+            Ok(c) => c,
+            Err(why) => {
+                println!("Unable to fetch a list of target channels: {:?}", why);
+                return;
+            }
+        };
+
+        for channel_id in channels.iter() {
+            let res = if moderate {
+                self.handle_channel(context.clone(), channel_id).await
+            } else {
+                self.forward_opportunities(context.clone(), channel_id)
+                    .await
+            };
+
+            if let Err(why) = res {
+                println!("Error forwarding opportunities to a channel: {:?}", why);
+            }
+        }
+    }
+
+    /// Get how long to wait before the next poll.
+    fn get_poll_delay(&self) -> Duration {
+        let polling = &self.config.polling;
+        let jitter = if polling.jitter_secs > 0 {
+            rand::thread_rng().gen_range(0..=polling.jitter_secs)
+        } else {
+            0
+        };
+
+        Duration::from_secs(polling.interval_secs.saturating_add(jitter))
+    }
+
+    /// Forward new opportunities to all target channels, forever, waiting
+    /// [crate::config::PollingConfig::interval_secs] (plus jitter) between checks.
+    async fn poll(self, context: Context) {
+        loop {
+            tokio::time::sleep(self.get_poll_delay()).await;
+            self.handle_all_channels(context.clone(), false).await;
+        }
+    }
+}
+
+/// Get the id of the discussion one of our messages forwarded, given the message's
+/// `content` and `embeds`. Returns `None` if the message doesn't start a forwarded
+/// discussion (for example, if it continues a long one).
+fn get_forwarded_id(source: &dyn DiscussionSource, content: &str, embeds: &[Embed]) -> Option<u64> {
+    // Embeds link to the discussion with their URL. Only the first embed of a
+    // discussion has one.
+    if let Some(embed) = embeds.first() {
+        let url = embed.url.as_deref()?;
+        return source.find_links(url).first().map(|link| link.get_id());
+    }
+
+    if is_continuation(content) {
+        return None;
+    }
+
+    // Text messages link to the discussion on their first line, after the title
+    // (or, for messages sent by older versions, after "Forwarded message from").
+    // The content may link to other discussions, so it's ignored.
+    let first_line = content.lines().next()?;
+    first_line
+        .split_whitespace()
+        .rev()
+        .flat_map(|word| word.rsplit("]("))
+        .find_map(|part| source.find_links(part).first().map(|link| link.get_id()))
+}
+
+/// Get when `post` was last edited, or `None` if it hasn't been.
+fn get_edit_time(post: &DiscussionPost) -> Option<DateTime<Utc>> {
+    post.get_edited_at().filter(|_| post.is_edited())
+}
+
+/// Get the title `post` is shown with.
+fn get_title(post: &DiscussionPost) -> String {
+    match post.get_title() {
+        Some(title) => title.to_string(),
+        None => format!("Discussion #{}", post.get_link().get_id()),
+    }
+}
+
+/// Escape `title` as markdown, so that it can be used as the text of a link
+/// (as in the default [crate::config::TemplateConfig::forwarded_message]).
+fn escape_title(title: &str) -> String {
+    DiscordRenderer
+        .escape(title, false)
+        .replace('(', "\\(")
+        .replace(')', "\\)")
+}
+
+/// Shorten `text` to at most `limit` characters, ending it with an ellipsis if shortened.
+fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+
+    let mut result: String = text.chars().take(limit - 1).collect();
+    result.push('…');
+    result
+}
+
+/// Format `time` so that Discord shows it relative to the reader's current time
+/// (e.g. "3 days ago").
+fn discord_timestamp(time: DateTime<Utc>) -> String {
+    format!("<t:{}:R>", time.timestamp())
 }
 
 #[async_trait]
@@ -216,6 +770,23 @@ impl EventHandler for Handler {
         }
 
         if self.is_target_channel(&name) {
+            if !self.config.moderation.delete_user_posts {
+                return;
+            }
+
+            let exempt = self
+                .is_exempt(&context, msg.guild_id, &msg, &mut HashMap::new())
+                .await;
+            match exempt {
+                Ok(false) => {}
+                Ok(true) => return,
+                Err(why) => {
+                    // Rather leave the message than delete a moderator's post.
+                    println!("Error checking whether a post is exempt! {:?}", why);
+                    return;
+                }
+            }
+
             // Delete the message & dm the author.
             println!("Message posted in the opportunities channel! Deleting and replying.");
 
@@ -237,36 +808,405 @@ impl EventHandler for Handler {
     /// and user.
     async fn ready(&self, context: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
-        let channels;
-        match self.get_target_channels(context.clone()).await {
-            Ok(c) => {
-                channels = c;
-            }
-            Err(why) => {
-                println!("Unable to fetch a list of target channels: {:?}", why);
-                return;
-            }
-        };
 
-        for channel_id in channels.iter() {
-            let res = self.handle_channel(context.clone(), channel_id).await;
-
-            if let Err(why) = res {
-                println!("Error forwarding opportunities to a channel: {:?}", why);
-            }
+        // Check for new opportunities periodically, not just when we (re)connect.
+        if !self.polling_started.swap(true, Ordering::SeqCst) {
+            tokio::spawn(self.clone().poll(context.clone()));
         }
+
+        self.handle_all_channels(context, true).await;
     }
 }
 
 /// Starts the forwarding bot.
 /// `token` should be gotten from Discord and will allow
 /// us to communicate with the Discord API.
-pub async fn start(token: String) {
+/// What is forwarded, and where, is described by `config`.
+pub async fn start(token: String, config: BotConfig) {
+    let store = state_store::open(&config.state).expect("Unable to open the forwarding state!");
+    let sources = discussion_source::from_config(&config).expect("Unable to create sources!");
+
     // Connect to Discord!
     let mut client = Client::builder(token)
-        .event_handler(Handler)
+        .event_handler(Handler::new(config, sources, store))
         .await
         .expect("Unable to connect to Discord!");
 
     client.start().await.expect("Bot stopped!");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{get_forwarded_id, truncate, ForwardedMessage, Handler, EMBED_DESCRIPTION_LIMIT};
+    use crate::config::BotConfig;
+    use crate::discussion_source::DiscussionSource;
+    use crate::github_scraper::{DiscussionLink, DiscussionPost, HtmlScraperSource, ScraperConfig};
+    use crate::message_split::MESSAGE_LIMIT;
+    use crate::state_store::{ForwardedRecord, MemoryStore, StateStore};
+    use async_trait::async_trait;
+    use serenity::builder::CreateEmbed;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A [DiscussionSource] with discussions numbered 1 through `count`, of which
+    /// those in `edited` have been edited.
+    struct FakeSource {
+        count: u64,
+        edited: Vec<u64>,
+    }
+
+    /// When [FakeSource]'s edited discussions were edited.
+    const EDITED_AT: &str = "2021-10-01T00:00:00Z";
+
+    #[async_trait]
+    impl DiscussionSource for FakeSource {
+        fn get_source_key(&self) -> String {
+            "fake".to_string()
+        }
+
+        fn get_post_to_url(&self) -> String {
+            "https://example.com/".to_string()
+        }
+
+        fn find_links(&self, _text: &str) -> Vec<DiscussionLink> {
+            Vec::new()
+        }
+
+        fn get_link(&self, id: u64) -> DiscussionLink {
+            DiscussionLink::new(format!("/fake/discussions/{}", id), id)
+        }
+
+        async fn list_since(
+            &self,
+            cursor: Option<u64>,
+        ) -> Result<Vec<DiscussionLink>, Box<dyn std::error::Error>> {
+            Ok((cursor.unwrap_or(0) + 1..=self.count)
+                .map(|id| self.get_link(id))
+                .collect())
+        }
+
+        async fn fetch(
+            &self,
+            link: &DiscussionLink,
+        ) -> Result<DiscussionPost, Box<dyn std::error::Error>> {
+            let post = DiscussionPost::new(
+                format!("Opportunity #{}", link.get_id()),
+                "someone".to_string(),
+                link.clone(),
+            );
+
+            if self.edited.contains(&link.get_id()) {
+                Ok(post.with_edited_at(EDITED_AT.parse().unwrap()))
+            } else {
+                Ok(post)
+            }
+        }
+    }
+
+    fn record(discussion_id: u64) -> ForwardedRecord {
+        ForwardedRecord {
+            channel_id: 1,
+            source: "fake".to_string(),
+            discussion_id,
+            message_ids: vec![],
+            edited_at: None,
+        }
+    }
+
+    fn ids(posts: &[DiscussionPost]) -> Vec<u64> {
+        posts.iter().map(|post| post.get_link().get_id()).collect()
+    }
+
+    #[test]
+    fn test_fill_embed() {
+        let handler = Handler::new(BotConfig::default(), vec![], Arc::new(MemoryStore::new()));
+        let link = DiscussionLink::new("/UWAppDev/community/discussions/5".to_string(), 5);
+        let post = DiscussionPost::new("Apply now!".to_string(), "someone".to_string(), link)
+            .with_title("Internship".to_string())
+            .with_labels(vec!["paid".to_string(), "remote".to_string()])
+            .with_author_avatar_url("https://avatars.githubusercontent.com/u/1".to_string());
+
+        let mut embed = CreateEmbed::default();
+        handler.fill_embed(&mut embed, &post, post.get_content());
+        let embed = &embed.0;
+
+        assert_eq!(embed["title"], "Internship");
+        assert_eq!(
+            embed["url"],
+            "https://github.com/UWAppDev/community/discussions/5"
+        );
+        assert_eq!(embed["description"], "Apply now!");
+        assert_eq!(embed["author"]["name"], "someone");
+        assert_eq!(
+            embed["author"]["icon_url"],
+            "https://avatars.githubusercontent.com/u/1"
+        );
+        assert_eq!(embed["fields"][0]["value"], "paid, remote");
+        assert_eq!(embed["footer"]["text"], "Discussion #5");
+        assert_eq!(embed["color"], 0x2ea44f);
+    }
+
+    #[test]
+    fn test_poll_delay_without_jitter() {
+        let mut config = BotConfig::default();
+        config.polling.interval_secs = 90;
+        config.polling.jitter_secs = 0;
+        let handler = Handler::new(config, vec![], Arc::new(MemoryStore::new()));
+
+        for _ in 0..10 {
+            assert_eq!(handler.get_poll_delay(), Duration::from_secs(90));
+        }
+    }
+
+    #[test]
+    fn test_poll_delay_with_jitter() {
+        let mut config = BotConfig::default();
+        config.polling.interval_secs = 90;
+        config.polling.jitter_secs = 5;
+        let handler = Handler::new(config, vec![], Arc::new(MemoryStore::new()));
+
+        // Jitter is only ever added to the interval.
+        for _ in 0..100 {
+            let delay = handler.get_poll_delay();
+            assert!(
+                delay >= Duration::from_secs(90) && delay <= Duration::from_secs(95),
+                "{:?} should be within 90s to 95s",
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn test_poll_delay_does_not_overflow() {
+        let mut config = BotConfig::default();
+        config.polling.interval_secs = u64::MAX;
+        config.polling.jitter_secs = 5;
+        let handler = Handler::new(config, vec![], Arc::new(MemoryStore::new()));
+
+        assert_eq!(handler.get_poll_delay(), Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn test_get_forwarded_id() {
+        let source =
+            HtmlScraperSource::new(ScraperConfig::new("UWAppDev", "community", "opportunities"));
+        let mut config = BotConfig::default();
+        config.embeds.enabled = false;
+        let handler = Handler::new(config, vec![], Arc::new(MemoryStore::new()));
+
+        // The body links to an older discussion, and is long enough to be split.
+        let link = DiscussionLink::new("/UWAppDev/community/discussions/7".to_string(), 7);
+        let content = format!(
+            "Follows up on https://github.com/UWAppDev/community/discussions/3\n\n{}",
+            "More details. https://github.com/UWAppDev/community/discussions/2\n\n".repeat(100)
+        );
+        let post = DiscussionPost::new(content, "someone".to_string(), link)
+            .with_title("Like github.com/UWAppDev/community/discussions/1".to_string());
+        let messages: Vec<String> = handler
+            .get_forwarded_messages(&post)
+            .into_iter()
+            .map(|message| match message {
+                ForwardedMessage::Text(text) => text,
+                ForwardedMessage::Embed(_) => panic!("Embeds are disabled"),
+            })
+            .collect();
+
+        assert!(messages.len() > 1);
+        assert_eq!(get_forwarded_id(&source, &messages[0], &[]), Some(7));
+        for message in &messages[1..] {
+            assert_eq!(get_forwarded_id(&source, message, &[]), None);
+        }
+
+        // Messages sent by older versions.
+        let old_message =
+            "**Forwarded message from** https://github.com/UWAppDev/community/discussions/5:\n\
+            **Author:** someone\n\nSee https://github.com/UWAppDev/community/discussions/4";
+        assert_eq!(get_forwarded_id(&source, old_message, &[]), Some(5));
+
+        // Only the first embed of a discussion has a URL.
+        let embed = serde_json::from_value(serde_json::json!({
+            "type": "rich",
+            "url": "https://github.com/UWAppDev/community/discussions/7",
+            "description": "See https://github.com/UWAppDev/community/discussions/3",
+        }))
+        .unwrap();
+        let continued = serde_json::from_value(serde_json::json!({
+            "type": "rich",
+            "description": "See https://github.com/UWAppDev/community/discussions/3",
+        }))
+        .unwrap();
+        assert_eq!(get_forwarded_id(&source, "", &[embed]), Some(7));
+        assert_eq!(get_forwarded_id(&source, "", &[continued]), None);
+    }
+
+    #[test]
+    fn test_forwarded_text_is_sanitized() {
+        let mut config = BotConfig::default();
+        config.embeds.enabled = false;
+        let handler = Handler::new(config, vec![], Arc::new(MemoryStore::new()));
+        let link = DiscussionLink::new("/UWAppDev/community/discussions/5".to_string(), 5);
+        let post = DiscussionPost::new("Hey @everyone!".to_string(), "someone".to_string(), link)
+            .with_title("Ask <@&123>".to_string());
+
+        let text = handler.get_forwarded_text(&post);
+        assert!(text.contains("Hey @\u{200B}everyone!"), "{}", text);
+        assert!(text.contains("Ask <\u{200B}@&123>"), "{}", text);
+
+        let link = DiscussionLink::new("/UWAppDev/community/discussions/6".to_string(), 6);
+        let post = DiscussionPost::new("Apply!".to_string(), "someone".to_string(), link)
+            .with_title("Intern [remote] (paid) || more".to_string());
+        let text = handler.get_forwarded_text(&post);
+        assert!(
+            text.starts_with(
+                "**[Intern \\[remote\\] \\(paid\\) \\|\\| more](https://github.com/UWAppDev/community/discussions/6)**"
+            ),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_long_posts_are_split() {
+        let mut config = BotConfig::default();
+        let link = DiscussionLink::new("/UWAppDev/community/discussions/5".to_string(), 5);
+        let content = "A paragraph about the opportunity.\n\n".repeat(200);
+        let post = DiscussionPost::new(content, "someone".to_string(), link);
+
+        config.embeds.enabled = false;
+        let handler = Handler::new(config.clone(), vec![], Arc::new(MemoryStore::new()));
+        let messages = handler.get_forwarded_messages(&post);
+        assert!(messages.len() > 1);
+        for message in messages {
+            match message {
+                ForwardedMessage::Text(text) => assert!(text.chars().count() <= MESSAGE_LIMIT),
+                ForwardedMessage::Embed(_) => panic!("Embeds are disabled"),
+            }
+        }
+
+        config.embeds.enabled = true;
+        let handler = Handler::new(config, vec![], Arc::new(MemoryStore::new()));
+        let messages = handler.get_forwarded_messages(&post);
+        assert_eq!(messages.len(), 2);
+        for (index, message) in messages.into_iter().enumerate() {
+            match message {
+                ForwardedMessage::Embed(embed) => {
+                    let description = embed.0["description"].as_str().unwrap();
+                    assert!(description.chars().count() <= EMBED_DESCRIPTION_LIMIT);
+                    assert_eq!(embed.0.contains_key("title"), index == 0);
+                }
+                ForwardedMessage::Text(_) => panic!("Expected an embed"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_embed_images() {
+        let handler = Handler::new(BotConfig::default(), vec![], Arc::new(MemoryStore::new()));
+        let link = DiscussionLink::new("/UWAppDev/community/discussions/5".to_string(), 5);
+        let post = DiscussionPost::new("Photos".to_string(), "someone".to_string(), link)
+            .with_images(vec![
+                "https://example.com/1.png".to_string(),
+                "https://example.com/2.png".to_string(),
+            ]);
+
+        let mut embed = CreateEmbed::default();
+        handler.fill_embed(&mut embed, &post, post.get_content());
+        let embed = &embed.0;
+
+        assert_eq!(embed["image"]["url"], "https://example.com/1.png");
+        assert_eq!(embed["fields"][0]["name"], "More images");
+        assert_eq!(
+            embed["fields"][0]["value"],
+            "[Image 2](https://example.com/2.png)\n"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("ééééé", 3), "éé…");
+    }
+
+    #[tokio::test]
+    async fn test_unforwarded_posts() {
+        let source = FakeSource {
+            count: 3,
+            edited: vec![],
+        };
+        let store = Arc::new(MemoryStore::new());
+        let handler = Handler::new(BotConfig::default(), vec![], store.clone());
+
+        let posts = handler.get_unforwarded_posts(1, &source).await.unwrap();
+        assert_eq!(ids(&posts), vec![1, 2, 3]);
+        assert_eq!(posts[0].get_content(), "Opportunity #1");
+
+        store.record_forwarded(record(2)).unwrap();
+        let posts = handler.get_unforwarded_posts(1, &source).await.unwrap();
+        assert_eq!(ids(&posts), vec![3]);
+
+        // Other channels are unaffected.
+        let posts = handler.get_unforwarded_posts(2, &source).await.unwrap();
+        assert_eq!(ids(&posts), vec![1, 2, 3]);
+
+        store.record_forwarded(record(3)).unwrap();
+        let posts = handler.get_unforwarded_posts(1, &source).await.unwrap();
+        assert!(posts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_edited_posts() {
+        let source = FakeSource {
+            count: 4,
+            edited: vec![1, 2, 3],
+        };
+        let store = Arc::new(MemoryStore::new());
+        let mut config = BotConfig::default();
+        config.polling.edit_check_count = 3;
+        let handler = Handler::new(config.clone(), vec![], store.clone());
+
+        // #1 is too old to be checked, and #3 was recovered from the channel's
+        // history, so has no messages to update.
+        for (id, message_ids) in [(1, vec![10]), (2, vec![20, 21]), (3, vec![]), (4, vec![40])] {
+            store
+                .record_forwarded(ForwardedRecord {
+                    message_ids,
+                    ..record(id)
+                })
+                .unwrap();
+        }
+
+        let edited = handler.get_edited_posts(1, &source).await.unwrap();
+        assert_eq!(edited.len(), 1);
+        let (edited_record, post) = &edited[0];
+        assert_eq!(edited_record.message_ids, vec![20, 21]);
+        assert_eq!(post.get_link().get_id(), 2);
+
+        // Once updated, the post isn't edited again.
+        store
+            .record_forwarded(ForwardedRecord {
+                edited_at: Some(EDITED_AT.parse().unwrap()),
+                ..edited_record.clone()
+            })
+            .unwrap();
+        assert!(handler
+            .get_edited_posts(1, &source)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Edits aren't checked for if disabled.
+        store
+            .record_forwarded(ForwardedRecord {
+                message_ids: vec![20],
+                ..record(2)
+            })
+            .unwrap();
+        config.polling.edit_check_count = 0;
+        let handler = Handler::new(config, vec![], store.clone());
+        assert!(handler
+            .get_edited_posts(1, &source)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,868 @@
+//! Loading and validating the bot's configuration file.
+//!
+//! The configuration is a TOML file. For example,
+//! ```toml
+//! [discord]
+//! token_env = "DISCORD_BOT_TOKEN"
+//! channels = ["opportunities"]
+//!
+//! [[sources]]
+//! owner = "UWAppDev"
+//! repo = "community"
+//! category = "opportunities"
+//!
+//! [polling]
+//! interval_secs = 600
+//! ```
+//! See `bot.example.toml` for a description of every option.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::github_scraper::ScraperConfig;
+use crate::html_walker::{DetailsStyle, MarkdownOptions};
+
+/// Environment variable that, if set, overrides the configured token.
+pub const ENV_VAR_TOKEN_NAME: &str = "DISCORD_BOT_TOKEN";
+
+/// Environment variable that, if set, overrides the first source's repository (as `owner/repo`).
+pub const ENV_VAR_REPOSITORY_NAME: &str = "OPPORTUNITIES_REPOSITORY";
+
+/// Environment variable that, if set, overrides the first source's discussion category.
+pub const ENV_VAR_CATEGORY_NAME: &str = "OPPORTUNITIES_CATEGORY";
+
+/// Environment variable that, if set, overrides the first source's GitHub host.
+pub const ENV_VAR_HOST_NAME: &str = "OPPORTUNITIES_GITHUB_HOST";
+
+/// Environment variable that, if set, overrides the target channel names (comma-separated).
+pub const ENV_VAR_CHANNELS_NAME: &str = "OPPORTUNITIES_CHANNELS";
+
+/// Environment variable that, if set, overrides the polling interval (in seconds).
+pub const ENV_VAR_POLL_INTERVAL_NAME: &str = "OPPORTUNITIES_POLL_INTERVAL_SECS";
+
+/// Placeholders that can be used in [TemplateConfig::forwarded_message].
+const FORWARDED_MESSAGE_PLACEHOLDERS: &[&str] =
+    &["title", "url", "author", "posted", "edited", "content"];
+
+/// Placeholders that can be used in [TemplateConfig::deleted_message].
+const DELETED_MESSAGE_PLACEHOLDERS: &[&str] = &["content", "post_to_url"];
+
+/// Configuration for the entire bot.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BotConfig {
+    #[serde(default)]
+    pub discord: DiscordConfig,
+
+    /// GitHub discussion categories to forward opportunities from.
+    #[serde(default = "default_sources")]
+    pub sources: Vec<ScraperConfig>,
+
+    #[serde(default)]
+    pub polling: PollingConfig,
+
+    #[serde(default)]
+    pub moderation: ModerationConfig,
+
+    #[serde(default)]
+    pub templates: TemplateConfig,
+
+    #[serde(default)]
+    pub state: StateConfig,
+
+    #[serde(default)]
+    pub github: GitHubConfig,
+
+    #[serde(default)]
+    pub embeds: EmbedConfig,
+
+    #[serde(default)]
+    pub formatting: FormattingConfig,
+}
+
+/// How the content of posts is converted from GitHub's HTML.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormattingConfig {
+    /// How collapsible `<details>` sections are shown.
+    pub details: DetailsStyle,
+
+    /// `<details>` sections longer than this many characters are cut short,
+    /// with a link to the rest of the post.
+    pub details_limit: Option<usize>,
+}
+
+/// How forwarded opportunities are displayed.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbedConfig {
+    /// True iff opportunities are sent as rich embeds. If false,
+    /// [TemplateConfig::forwarded_message] is sent as plain text.
+    pub enabled: bool,
+
+    /// The embed color (as `#rrggbb`) for categories not in [EmbedConfig::category_colors].
+    pub color: String,
+
+    /// Embed colors (as `#rrggbb`), by discussion category slug.
+    pub category_colors: HashMap<String, String>,
+}
+
+/// How discussions are fetched from GitHub.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceApi {
+    /// Scrape the discussions' web pages.
+    #[default]
+    Html,
+
+    /// Use the GitHub GraphQL API. Requires a token (see [GitHubConfig]).
+    GraphQl,
+}
+
+/// Access to the GitHub API, used by sources with `api = "graphql"`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitHubConfig {
+    /// A GitHub token able to read the sources' discussions.
+    /// Prefer [GitHubConfig::token_env] to keep the token out of the configuration file.
+    pub token: Option<String>,
+
+    /// Name of an environment variable containing the token.
+    pub token_env: String,
+
+    /// Where to send GraphQL queries, if not GitHub's API for the source's host.
+    pub graphql_endpoint: Option<String>,
+}
+
+/// How to connect to Discord and which channels to manage.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscordConfig {
+    /// The bot token. Prefer [DiscordConfig::token_env] or [DiscordConfig::token_file]
+    /// to keep the token out of the configuration file.
+    pub token: Option<String>,
+
+    /// Name of an environment variable containing the token.
+    pub token_env: Option<String>,
+
+    /// Path to a file containing the token.
+    pub token_file: Option<PathBuf>,
+
+    /// Names of the channels opportunities are forwarded to.
+    pub channels: Vec<String>,
+}
+
+/// How often to check GitHub for new opportunities.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PollingConfig {
+    /// Seconds between checks.
+    pub interval_secs: u64,
+
+    /// Up to this many seconds are randomly added to each interval.
+    pub jitter_secs: u64,
+
+    /// How many of the discussions most recently forwarded to each channel are
+    /// checked for edits, which are copied to their messages. Zero disables this.
+    pub edit_check_count: usize,
+}
+
+/// What to do with messages users post in the target channels.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModerationConfig {
+    /// True iff messages not posted by the bot should be deleted.
+    pub delete_user_posts: bool,
+
+    /// True iff the authors of deleted messages should be sent an explanation.
+    pub dm_author: bool,
+
+    /// Ids of roles whose members may post in the target channels (e.g. moderators).
+    pub exempt_roles: Vec<u64>,
+
+    /// Ids of users who may post in the target channels.
+    pub exempt_users: Vec<u64>,
+}
+
+/// Text of the messages sent by the bot. `{name}` is replaced with the value of `name`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplateConfig {
+    /// Sent to target channels for each opportunity.
+    /// Placeholders: `{title}`, `{url}`, `{author}`, `{posted}` (e.g. "3 days ago"),
+    /// `{edited}` (e.g. "edited 2 hours ago", or empty if not edited) and `{content}`.
+    /// Keep `{url}` on the first line: it's read back from the channel's history when
+    /// nothing has been recorded for the channel.
+    pub forwarded_message: String,
+
+    /// Sent to users whose messages were deleted.
+    /// Placeholders: `{content}` and `{post_to_url}`.
+    pub deleted_message: String,
+}
+
+/// Where to remember which opportunities have been forwarded.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StateConfig {
+    pub backend: StateBackend,
+
+    /// Where the state is stored. Defaults to a file in the working directory.
+    pub path: Option<PathBuf>,
+}
+
+/// Kinds of [crate::state_store::StateStore].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateBackend {
+    /// Don't persist anything. Opportunities forwarded before the bot
+    /// was started are recovered from channel history.
+    Memory,
+
+    /// A JSON file.
+    Json,
+
+    /// A SQLite database. Requires the `sqlite` feature.
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// Errors encountered while loading the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Unable to read a file.
+    Io(PathBuf, std::io::Error),
+
+    /// The configuration file is not valid TOML or has unexpected fields.
+    Parse(toml::de::Error),
+
+    /// The configuration is well-formed, but its values don't make sense.
+    Invalid(String),
+
+    /// No token was given in the configuration or the environment.
+    MissingToken,
+}
+
+fn default_sources() -> Vec<ScraperConfig> {
+    vec![ScraperConfig::default()]
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        BotConfig {
+            discord: Default::default(),
+            sources: default_sources(),
+            polling: Default::default(),
+            moderation: Default::default(),
+            templates: Default::default(),
+            state: Default::default(),
+            github: Default::default(),
+            embeds: Default::default(),
+            formatting: Default::default(),
+        }
+    }
+}
+
+impl Default for EmbedConfig {
+    fn default() -> Self {
+        EmbedConfig {
+            enabled: true,
+            color: "#2ea44f".to_string(),
+            category_colors: HashMap::new(),
+        }
+    }
+}
+
+impl EmbedConfig {
+    /// Get the color for embeds of discussions in `category`.
+    /// Invalid colors are treated as black.
+    pub fn get_color(&self, category: Option<&str>) -> u32 {
+        let color = category
+            .and_then(|category| self.category_colors.get(category))
+            .unwrap_or(&self.color);
+
+        parse_color(color).unwrap_or(0)
+    }
+}
+
+/// Parse a color of the form `#rrggbb` (the `#` is optional).
+pub fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+
+    u32::from_str_radix(hex, 16).ok()
+}
+
+impl FormattingConfig {
+    /// Get the options for converting the post found at `post_url`.
+    /// Relative links in the post are resolved against `post_url`.
+    pub fn markdown_options(&self, post_url: &str) -> MarkdownOptions {
+        MarkdownOptions {
+            base_url: Some(post_url.to_string()),
+            details: self.details,
+            details_limit: self.details_limit,
+            read_more_url: Some(post_url.to_string()),
+        }
+    }
+}
+
+impl Default for GitHubConfig {
+    fn default() -> Self {
+        GitHubConfig {
+            token: None,
+            token_env: "GITHUB_TOKEN".to_string(),
+            graphql_endpoint: None,
+        }
+    }
+}
+
+impl GitHubConfig {
+    /// Get the GitHub token, preferring [GitHubConfig::token] to [GitHubConfig::token_env].
+    pub fn resolve_token(&self) -> Result<String, ConfigError> {
+        if let Some(token) = &self.token {
+            return Ok(token.clone());
+        }
+
+        std::env::var(&self.token_env).map_err(|_| {
+            ConfigError::Invalid(format!(
+                "sources with api = \"graphql\" need a GitHub token. Set {} or token in [github].",
+                self.token_env
+            ))
+        })
+    }
+}
+
+impl Default for DiscordConfig {
+    fn default() -> Self {
+        DiscordConfig {
+            token: None,
+            token_env: None,
+            token_file: None,
+            channels: vec!["opportunities".to_string()],
+        }
+    }
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        PollingConfig {
+            interval_secs: 600,
+            jitter_secs: 60,
+            edit_check_count: 5,
+        }
+    }
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        ModerationConfig {
+            delete_user_posts: true,
+            dm_author: true,
+            exempt_roles: Vec::new(),
+            exempt_users: Vec::new(),
+        }
+    }
+}
+
+impl ModerationConfig {
+    /// Returns true iff the user `user_id`, who has the roles `role_ids`,
+    /// may post in the target channels.
+    pub fn is_exempt(&self, user_id: u64, role_ids: &[u64]) -> bool {
+        self.exempt_users.contains(&user_id)
+            || role_ids.iter().any(|role| self.exempt_roles.contains(role))
+    }
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        TemplateConfig {
+            forwarded_message: "**[{title}]({url})**\n**Author:** {author}, posted {posted} {edited}\n\n{content}"
+                .to_string(),
+            deleted_message: "I've deleted your message from the opportunities channel. It said: \n\n{content}\n\nPlease post opportunities here: {post_to_url}".to_string(),
+        }
+    }
+}
+
+impl Default for StateConfig {
+    fn default() -> Self {
+        StateConfig {
+            backend: StateBackend::Json,
+            path: None,
+        }
+    }
+}
+
+impl StateConfig {
+    /// Get the configured path, or the default path for the backend.
+    pub fn get_path(&self) -> PathBuf {
+        match &self.path {
+            Some(path) => path.clone(),
+            None => match self.backend {
+                #[cfg(feature = "sqlite")]
+                StateBackend::Sqlite => PathBuf::from("forwarding_state.sqlite"),
+                _ => PathBuf::from("forwarding_state.json"),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, why) => write!(f, "Unable to read {}: {}", path.display(), why),
+            ConfigError::Parse(why) => write!(f, "Invalid configuration file: {}", why),
+            ConfigError::Invalid(why) => write!(f, "Invalid configuration: {}", why),
+            ConfigError::MissingToken => write!(
+                f,
+                "No API token provided. Set {}, or one of token, token_env or token_file in [discord].",
+                ENV_VAR_TOKEN_NAME
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, why) => Some(why),
+            ConfigError::Parse(why) => Some(why),
+            _ => None,
+        }
+    }
+}
+
+impl BotConfig {
+    /// Parse a configuration from the text of a TOML file.
+    /// The result has not been validated.
+    pub fn from_toml(text: &str) -> Result<BotConfig, ConfigError> {
+        toml::from_str(text).map_err(ConfigError::Parse)
+    }
+
+    /// Read the configuration file at `path` (or use the defaults, if `None`),
+    /// apply overrides from the environment, and validate the result.
+    pub fn load(path: Option<&Path>) -> Result<BotConfig, ConfigError> {
+        let mut config = match path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|why| ConfigError::Io(path.to_path_buf(), why))?;
+                Self::from_toml(&text)?
+            }
+            None => BotConfig::default(),
+        };
+
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Override values in this with those given by `lookup`, which maps environment
+    /// variable names to their values.
+    pub fn apply_env<F>(&mut self, lookup: F) -> Result<(), ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(token) = lookup(ENV_VAR_TOKEN_NAME) {
+            self.discord.token = Some(token);
+        }
+
+        if let Some(repository) = lookup(ENV_VAR_REPOSITORY_NAME) {
+            let (owner, repo) = match repository.split_once('/') {
+                Some((owner, repo)) if !owner.is_empty() && !repo.is_empty() => (owner, repo),
+                _ => {
+                    return Err(ConfigError::Invalid(format!(
+                        "{} must be of the form owner/repo, not {:?}",
+                        ENV_VAR_REPOSITORY_NAME, repository
+                    )));
+                }
+            };
+
+            let source = self.first_source_mut();
+            source.owner = owner.to_string();
+            source.repo = repo.to_string();
+        }
+
+        if let Some(category) = lookup(ENV_VAR_CATEGORY_NAME) {
+            self.first_source_mut().category = category;
+        }
+
+        if let Some(host) = lookup(ENV_VAR_HOST_NAME) {
+            self.first_source_mut().host = host;
+        }
+
+        if let Some(channels) = lookup(ENV_VAR_CHANNELS_NAME) {
+            self.discord.channels = channels
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect();
+        }
+
+        if let Some(interval) = lookup(ENV_VAR_POLL_INTERVAL_NAME) {
+            self.polling.interval_secs = interval.trim().parse().map_err(|_| {
+                ConfigError::Invalid(format!(
+                    "{} must be a whole number of seconds, not {:?}",
+                    ENV_VAR_POLL_INTERVAL_NAME, interval
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the first source, creating a default source if there are none.
+    fn first_source_mut(&mut self) -> &mut ScraperConfig {
+        if self.sources.is_empty() {
+            self.sources.push(ScraperConfig::default());
+        }
+
+        &mut self.sources[0]
+    }
+
+    /// Returns an error describing the first problem with this configuration, if any.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::Invalid(message));
+
+        if self.discord.channels.is_empty() {
+            return invalid("[discord] must list at least one channel".to_string());
+        }
+
+        if self
+            .discord
+            .channels
+            .iter()
+            .any(|name| name.trim().is_empty())
+        {
+            return invalid("[discord] channel names must not be empty".to_string());
+        }
+
+        if self.sources.is_empty() {
+            return invalid("at least one [[sources]] entry is required".to_string());
+        }
+
+        for (index, source) in self.sources.iter().enumerate() {
+            let fields = [
+                ("owner", &source.owner),
+                ("repo", &source.repo),
+                ("category", &source.category),
+                ("host", &source.host),
+            ];
+
+            for (name, value) in fields.iter() {
+                if value.trim().is_empty() {
+                    return invalid(format!("sources[{}].{} must not be empty", index, name));
+                }
+            }
+
+            if source.host.contains("://") || source.host.contains('/') {
+                return invalid(format!(
+                    "sources[{}].host should be a host name (like github.com), not {:?}",
+                    index, source.host
+                ));
+            }
+        }
+
+        if self
+            .sources
+            .iter()
+            .any(|source| source.api == SourceApi::GraphQl)
+        {
+            self.github.resolve_token()?;
+        }
+
+        if self.polling.interval_secs == 0 {
+            return invalid("[polling] interval_secs must be greater than zero".to_string());
+        }
+
+        let colors = std::iter::once(("color".to_string(), &self.embeds.color)).chain(
+            self.embeds
+                .category_colors
+                .iter()
+                .map(|(category, color)| (format!("category_colors.{}", category), color)),
+        );
+        for (name, color) in colors {
+            if parse_color(color).is_none() {
+                return invalid(format!(
+                    "[embeds] {} should be a color of the form #rrggbb, not {:?}",
+                    name, color
+                ));
+            }
+        }
+
+        if self.formatting.details_limit == Some(0) {
+            return invalid("[formatting] details_limit must be greater than zero".to_string());
+        }
+
+        check_placeholders(
+            "forwarded_message",
+            &self.templates.forwarded_message,
+            FORWARDED_MESSAGE_PLACEHOLDERS,
+        )?;
+        check_placeholders(
+            "deleted_message",
+            &self.templates.deleted_message,
+            DELETED_MESSAGE_PLACEHOLDERS,
+        )?;
+
+        Ok(())
+    }
+
+    /// Get the Discord token, using (in order of preference) the
+    /// [DiscordConfig::token], [DiscordConfig::token_env] and [DiscordConfig::token_file].
+    pub fn resolve_token(&self) -> Result<String, ConfigError> {
+        if let Some(token) = &self.discord.token {
+            return Ok(token.clone());
+        }
+
+        if let Some(name) = &self.discord.token_env {
+            if let Ok(token) = std::env::var(name) {
+                return Ok(token);
+            }
+        }
+
+        if let Some(path) = &self.discord.token_file {
+            let token =
+                std::fs::read_to_string(path).map_err(|why| ConfigError::Io(path.clone(), why))?;
+            return Ok(token.trim().to_string());
+        }
+
+        Err(ConfigError::MissingToken)
+    }
+}
+
+/// Replace each `{name}` in `template` with its value in `values`.
+/// Unknown placeholders are left as-is.
+pub fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let values: HashMap<&str, &str> = values.iter().cloned().collect();
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after_brace = &rest[start + 1..];
+
+        match after_brace.find('}') {
+            Some(end) if values.contains_key(&after_brace[..end]) => {
+                result.push_str(values[&after_brace[..end]]);
+                rest = &after_brace[end + 1..];
+            }
+            _ => {
+                result.push('{');
+                rest = after_brace;
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Returns an error if `template` contains a `{placeholder}` not in `allowed`.
+fn check_placeholders(name: &str, template: &str, allowed: &[&str]) -> Result<(), ConfigError> {
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let after_brace = &rest[start + 1..];
+        let end = match after_brace.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        let placeholder = &after_brace[..end];
+
+        let looks_like_placeholder = !placeholder.is_empty()
+            && placeholder
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if looks_like_placeholder && !allowed.contains(&placeholder) {
+            return Err(ConfigError::Invalid(format!(
+                "[templates] {} uses unknown placeholder {{{}}}. Available: {}",
+                name,
+                placeholder,
+                allowed
+                    .iter()
+                    .map(|p| format!("{{{}}}", p))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )));
+        }
+
+        rest = &after_brace[end + 1..];
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill_template, BotConfig, ConfigError, DetailsStyle, SourceApi};
+
+    #[test]
+    fn test_parse_full_config() {
+        let config = BotConfig::from_toml(
+            r##"
+[discord]
+token = "abc"
+channels = ["opportunities", "jobs"]
+
+[[sources]]
+owner = "UWAppDev"
+repo = "community"
+
+[[sources]]
+owner = "someone"
+repo = "fork"
+category = "jobs"
+host = "github.example.com"
+api = "graphql"
+
+[github]
+token = "github token"
+
+[polling]
+interval_secs = 30
+
+[moderation]
+dm_author = false
+exempt_roles = [100]
+exempt_users = [200]
+
+[embeds]
+color = "#000010"
+category_colors = { jobs = "ff0000" }
+
+[formatting]
+details = "spoiler"
+details_limit = 500
+
+[templates]
+forwarded_message = "{url} by {author}"
+"##,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.discord.channels, vec!["opportunities", "jobs"]);
+        assert_eq!(config.sources.len(), 2);
+        assert_eq!(config.sources[0].category, "opportunities");
+        assert_eq!(config.sources[0].host, "github.com");
+        assert_eq!(config.sources[1].host, "github.example.com");
+        assert_eq!(config.sources[0].api, SourceApi::Html);
+        assert_eq!(config.sources[1].api, SourceApi::GraphQl);
+        assert_eq!(config.github.resolve_token().unwrap(), "github token");
+        assert_eq!(config.polling.interval_secs, 30);
+        assert_eq!(config.polling.jitter_secs, 60);
+        assert!(config.moderation.delete_user_posts);
+        assert!(!config.moderation.dm_author);
+        assert!(config.moderation.is_exempt(1, &[5, 100]));
+        assert!(config.moderation.is_exempt(200, &[]));
+        assert!(!config.moderation.is_exempt(1, &[5]));
+        assert_eq!(config.resolve_token().unwrap(), "abc");
+        assert!(config.embeds.enabled);
+        assert_eq!(config.embeds.get_color(Some("jobs")), 0xff0000);
+        assert_eq!(config.embeds.get_color(Some("opportunities")), 0x10);
+        assert_eq!(config.embeds.get_color(None), 0x10);
+        assert_eq!(config.formatting.details, DetailsStyle::Spoiler);
+        assert_eq!(config.formatting.details_limit, Some(500));
+    }
+
+    #[test]
+    fn test_unknown_field_is_an_error() {
+        let result = BotConfig::from_toml("[polling]\ninterval = 30\n");
+        assert!(matches!(result, Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let config = BotConfig::from_toml("[polling]\ninterval_secs = 0\n").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let config = BotConfig::from_toml("[discord]\nchannels = []\n").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let config = BotConfig::from_toml("[embeds]\ncolor = \"green\"\n").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let config = BotConfig::from_toml("[formatting]\ndetails_limit = 0\n").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let config = BotConfig::from_toml("[templates]\nforwarded_message = \"{date}\"\n").unwrap();
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("{date}"), "{}", error);
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = BotConfig::from_toml(
+            r#"
+[discord]
+token = "from file"
+
+[[sources]]
+owner = "UWAppDev"
+repo = "community"
+"#,
+        )
+        .unwrap();
+
+        config
+            .apply_env(|name| match name {
+                "DISCORD_BOT_TOKEN" => Some("from env".to_string()),
+                "OPPORTUNITIES_REPOSITORY" => Some("someone/fork".to_string()),
+                "OPPORTUNITIES_CHANNELS" => Some("a, b".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.resolve_token().unwrap(), "from env");
+        assert_eq!(config.sources[0].owner, "someone");
+        assert_eq!(config.sources[0].repo, "fork");
+        assert_eq!(config.discord.channels, vec!["a", "b"]);
+
+        let result = config.apply_env(|name| match name {
+            "OPPORTUNITIES_REPOSITORY" => Some("no-slash".to_string()),
+            _ => None,
+        });
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_example_config_is_valid() {
+        let config = BotConfig::from_toml(include_str!("../bot.example.toml")).unwrap();
+        config.validate().unwrap();
+
+        let defaults = BotConfig::default();
+        assert_eq!(config.sources, defaults.sources);
+        assert_eq!(
+            config.templates.forwarded_message,
+            defaults.templates.forwarded_message
+        );
+        assert_eq!(
+            config.templates.deleted_message,
+            defaults.templates.deleted_message
+        );
+        assert_eq!(
+            config.moderation.exempt_roles,
+            defaults.moderation.exempt_roles
+        );
+        assert_eq!(
+            config.moderation.exempt_users,
+            defaults.moderation.exempt_users
+        );
+        assert_eq!(config.formatting.details, defaults.formatting.details);
+        assert_eq!(
+            config.formatting.details_limit,
+            defaults.formatting.details_limit
+        );
+    }
+
+    #[test]
+    fn test_fill_template() {
+        assert_eq!(
+            fill_template("{a} and {b}, not {c} or {", &[("a", "1"), ("b", "{a}")]),
+            "1 and {a}, not {c} or {"
+        );
+    }
+}
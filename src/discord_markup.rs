@@ -0,0 +1,216 @@
+//! Neutralizes Discord-specific markup in text from elsewhere (e.g. GitHub).
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::ops::Range;
+
+/// Inserted to break up mentions without visibly changing them.
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+/// Escape everything in `text` that Discord would treat specially, but GitHub
+/// would not: mass mentions (`@everyone`, `@here`), user, role, channel and
+/// command mentions, custom emoji and spoilers. Code blocks, inline code and
+/// bare URLs are left as-is, since Discord doesn't format their contents.
+///
+/// For example:
+/// ```
+/// # use forwarding_bot::discord_markup::sanitize;
+/// assert_eq!(sanitize("Hi @everyone!"), "Hi @\u{200B}everyone!");
+/// assert_eq!(sanitize("`@everyone`"), "`@everyone`");
+/// ```
+pub fn sanitize(text: &str) -> String {
+    sanitize_with(text, true)
+}
+
+/// Like [sanitize], but leaves spoilers alone. For text that has already been
+/// escaped as markdown (e.g. by [crate::renderer::DiscordRenderer]), where any
+/// spoilers are intended.
+pub fn sanitize_mentions(text: &str) -> String {
+    sanitize_with(text, false)
+}
+
+fn sanitize_with(text: &str, escape_spoilers: bool) -> String {
+    let mut result = String::new();
+    let mut last_end = 0;
+    for code in find_code(text) {
+        result.push_str(&sanitize_outside_urls(
+            &text[last_end..code.start],
+            escape_spoilers,
+        ));
+        result.push_str(&text[code.clone()]);
+        last_end = code.end;
+    }
+    result.push_str(&sanitize_outside_urls(&text[last_end..], escape_spoilers));
+
+    result
+}
+
+/// Find the byte ranges of the code in `text`. Code starts with a run of backticks
+/// and ends with the next run of the same length. Inline code (one or two backticks)
+/// can't span lines; an unclosed code block (three or more) runs to the end of `text`.
+fn find_code(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut index = 0;
+
+    while let Some(offset) = text[index..].find('`') {
+        let start = index + offset;
+        let fence_len = backtick_run_len(&text[start..]);
+        let mut search_from = start + fence_len;
+        index = search_from;
+
+        loop {
+            let closing = match text[search_from..].find('`') {
+                Some(offset) => search_from + offset,
+                None => {
+                    if fence_len >= 3 {
+                        ranges.push(start..text.len());
+                        index = text.len();
+                    }
+                    break;
+                }
+            };
+            if fence_len < 3 && text[search_from..closing].contains('\n') {
+                break;
+            }
+
+            let closing_len = backtick_run_len(&text[closing..]);
+            search_from = closing + closing_len;
+            if closing_len == fence_len {
+                ranges.push(start..search_from);
+                index = search_from;
+                break;
+            }
+        }
+    }
+
+    ranges
+}
+
+/// Get the number of backticks `text` starts with.
+fn backtick_run_len(text: &str) -> usize {
+    text.len() - text.trim_start_matches('`').len()
+}
+
+/// Find the bare URLs in `text`. Discord links these automatically, so they
+/// shouldn't be changed by escaping.
+pub fn find_urls(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    lazy_static! {
+        static ref BARE_URL: Regex = Regex::new(r"https?://[^\s<>|]+").unwrap();
+    }
+
+    BARE_URL.find_iter(text).map(|url| url.range())
+}
+
+/// Sanitize `text`, which contains no code, leaving its URLs unchanged.
+fn sanitize_outside_urls(text: &str, escape_spoilers: bool) -> String {
+    let mut result = String::new();
+    let mut last_end = 0;
+    for url in find_urls(text) {
+        result.push_str(&sanitize_prose(&text[last_end..url.start], escape_spoilers));
+        result.push_str(&text[url.clone()]);
+        last_end = url.end;
+    }
+    result.push_str(&sanitize_prose(&text[last_end..], escape_spoilers));
+
+    result
+}
+
+/// Sanitize `text`, which contains no code or URLs.
+fn sanitize_prose(text: &str, escape_spoilers: bool) -> String {
+    lazy_static! {
+        static ref MASS_MENTION: Regex = Regex::new(r"@(everyone|here)\b").unwrap();
+
+        // User (<@123>, <@!123>), role (<@&123>), channel (<#123>) and command
+        // (</name:123>) mentions, as well as custom emoji (<:name:123>, <a:name:123>).
+        static ref ID_MARKUP: Regex =
+            Regex::new(r"<(@[!&]?\d+|#\d+|/[\w -]+:\d+|a?:\w+:\d+)>").unwrap();
+    }
+
+    let text = MASS_MENTION.replace_all(text, |captures: &Captures| {
+        format!("@{}{}", ZERO_WIDTH_SPACE, &captures[1])
+    });
+    let text = ID_MARKUP.replace_all(&text, |captures: &Captures| {
+        format!("<{}{}>", ZERO_WIDTH_SPACE, &captures[1])
+    });
+
+    // GitHub has no spoilers.
+    if escape_spoilers {
+        text.replace("||", "\\|\\|")
+    } else {
+        text.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sanitize, sanitize_mentions};
+
+    #[test]
+    fn test_sanitize_mentions() {
+        assert_eq!(
+            sanitize("@everyone and @here, see <#123> (ask <@&456> or <@!789>)"),
+            "@\u{200B}everyone and @\u{200B}here, see <\u{200B}#123> \
+             (ask <\u{200B}@&456> or <\u{200B}@!789>)"
+        );
+        assert_eq!(
+            sanitize("Run </apply:42> <:party:7>"),
+            "Run <\u{200B}/apply:42> <\u{200B}:party:7>"
+        );
+
+        // Discord finds mass mentions even within other text, like email addresses.
+        assert_eq!(
+            sanitize("email me@everyone.example"),
+            "email me@\u{200B}everyone.example"
+        );
+
+        // Not Discord mentions.
+        assert_eq!(sanitize("ask @someone"), "ask @someone");
+        assert_eq!(sanitize("<https://example.com>"), "<https://example.com>");
+    }
+
+    #[test]
+    fn test_sanitize_spoilers() {
+        assert_eq!(sanitize("a || b"), "a \\|\\| b");
+        assert_eq!(sanitize_mentions("||@here||"), "||@\u{200B}here||");
+    }
+
+    #[test]
+    fn test_double_backtick_code_is_unchanged() {
+        assert_eq!(
+            sanitize("``a`@everyone`` @here"),
+            "``a`@everyone`` @\u{200B}here"
+        );
+        assert_eq!(sanitize("`` `<@123>` ``"), "`` `<@123>` ``");
+
+        // Runs of a different length don't close code.
+        assert_eq!(sanitize("```@here`` ||\n```"), "```@here`` ||\n```");
+        assert_eq!(sanitize("`a `` @here"), "`a `` @\u{200B}here");
+    }
+
+    #[test]
+    fn test_urls_are_unchanged() {
+        assert_eq!(
+            sanitize("See https://example.com/@everyone/issues and @everyone"),
+            "See https://example.com/@everyone/issues and @\u{200B}everyone"
+        );
+
+        // Spoilers around a URL are still escaped.
+        assert_eq!(
+            sanitize("||https://example.com/a_b||"),
+            "\\|\\|https://example.com/a_b\\|\\|"
+        );
+        assert_eq!(
+            sanitize("https://example.com/@here?q=1, <@&456>"),
+            "https://example.com/@here?q=1, <\u{200B}@&456>"
+        );
+    }
+
+    #[test]
+    fn test_code_is_unchanged() {
+        let text = "Use `@everyone` or\n```\nif a || b { ping(\"@here\") }\n```\n@here";
+        assert_eq!(
+            sanitize(text),
+            "Use `@everyone` or\n```\nif a || b { ping(\"@here\") }\n```\n@\u{200B}here"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Abstracts over where opportunities come from.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::config::{BotConfig, ConfigError, SourceApi};
+use crate::github_api::GraphQlSource;
+use crate::github_scraper::{DiscussionLink, DiscussionPost, HtmlScraperSource};
+
+/// Somewhere discussions can be listed and fetched from.
+#[async_trait]
+pub trait DiscussionSource: Send + Sync {
+    /// Get a string that identifies this source, for remembering which of its
+    /// discussions have been forwarded.
+    fn get_source_key(&self) -> String;
+
+    /// Where _users_ should post new opportunities.
+    fn get_post_to_url(&self) -> String;
+
+    /// Find all links to this source's discussions in `text`.
+    fn find_links(&self, text: &str) -> Vec<DiscussionLink>;
+
+    /// Get a link to this source's discussion numbered `id`.
+    fn get_link(&self, id: u64) -> DiscussionLink;
+
+    /// List the discussions with ids greater than `cursor` (or all discussions, if `None`),
+    /// sorted by increasing id.
+    async fn list_since(
+        &self,
+        cursor: Option<u64>,
+    ) -> Result<Vec<DiscussionLink>, Box<dyn std::error::Error>>;
+
+    /// Fetch the discussion `link` points to.
+    async fn fetch(
+        &self,
+        link: &DiscussionLink,
+    ) -> Result<DiscussionPost, Box<dyn std::error::Error>>;
+}
+
+/// Create the sources described by `config`.
+pub fn from_config(config: &BotConfig) -> Result<Vec<Arc<dyn DiscussionSource>>, ConfigError> {
+    let mut result: Vec<Arc<dyn DiscussionSource>> = Vec::new();
+
+    for source in config.sources.iter() {
+        match source.api {
+            SourceApi::Html => {
+                let html_source = HtmlScraperSource::new(source.clone())
+                    .with_formatting(config.formatting.clone());
+
+                result.push(Arc::new(html_source));
+            }
+            SourceApi::GraphQl => {
+                let mut api_source =
+                    GraphQlSource::new(source.clone(), config.github.resolve_token()?)
+                        .with_formatting(config.formatting.clone());
+                if let Some(endpoint) = &config.github.graphql_endpoint {
+                    api_source = api_source.with_endpoint(endpoint.clone());
+                }
+
+                result.push(Arc::new(api_source));
+            }
+        }
+    }
+
+    Ok(result)
+}
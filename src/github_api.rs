@@ -0,0 +1,696 @@
+//! Fetches discussions using GitHub's GraphQL API, rather than by scraping HTML.
+//!
+//! Unlike [crate::github_scraper], this doesn't break when GitHub changes its
+//! markup, but requires a token (any token able to read the repository's discussions).
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::config::FormattingConfig;
+use crate::discussion_source::DiscussionSource;
+use crate::github_scraper::{DiscussionLink, DiscussionPost, LinkMatcher, ScraperConfig};
+use crate::html_walker::html_to_md_minimal_with_options;
+
+/// How many discussions to request at once.
+const PAGE_SIZE: u32 = 50;
+
+/// Fields requested for each discussion.
+macro_rules! DISCUSSION_FIELDS {
+    () => {
+        "number title url createdAt lastEditedAt body bodyHTML author { login avatarUrl } category { slug } labels(first: 20) { nodes { name } }"
+    };
+}
+
+const CATEGORY_ID_QUERY: &str = "
+query($owner: String!, $repo: String!, $category: String!) {
+  repository(owner: $owner, name: $repo) {
+    discussionCategory(slug: $category) { id }
+  }
+}";
+
+const DISCUSSIONS_QUERY: &str = concat!(
+    "
+query($owner: String!, $repo: String!, $categoryId: ID!, $first: Int!, $after: String) {
+  repository(owner: $owner, name: $repo) {
+    discussions(categoryId: $categoryId, first: $first, after: $after, orderBy: {field: CREATED_AT, direction: DESC}) {
+      pageInfo { hasNextPage endCursor }
+      nodes { ",
+    DISCUSSION_FIELDS!(),
+    " }
+    }
+  }
+}"
+);
+
+const DISCUSSION_QUERY: &str = concat!(
+    "
+query($owner: String!, $repo: String!, $number: Int!) {
+  repository(owner: $owner, name: $repo) {
+    discussion(number: $number) { ",
+    DISCUSSION_FIELDS!(),
+    " }
+  }
+}"
+);
+
+/// Fetches discussions from the category described by a [ScraperConfig]
+/// using the GitHub GraphQL API.
+pub struct GraphQlSource {
+    config: ScraperConfig,
+    links: LinkMatcher,
+    token: String,
+    endpoint: String,
+    client: reqwest::Client,
+    formatting: FormattingConfig,
+
+    /// The node id of the discussion category, once looked up.
+    category_id: Mutex<Option<String>>,
+
+    /// Posts fetched while listing discussions, by id. These don't need
+    /// to be fetched again when forwarded.
+    recent_posts: Mutex<HashMap<u64, DiscussionPost>>,
+}
+
+/// An error reported by the GraphQL API, or a response we didn't expect.
+#[derive(Debug)]
+pub struct GraphQlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: Option<T>,
+    errors: Option<Vec<ErrorMessage>>,
+}
+
+#[derive(Deserialize)]
+struct ErrorMessage {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RepositoryData<T> {
+    repository: Option<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CategoryRepository {
+    discussion_category: Option<Node>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct DiscussionsRepository {
+    discussions: Page<Discussion>,
+}
+
+#[derive(Deserialize)]
+struct DiscussionRepository {
+    discussion: Option<Discussion>,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Page<T> {
+    page_info: PageInfo,
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Discussion {
+    number: u64,
+    title: String,
+    url: String,
+    created_at: DateTime<Utc>,
+    last_edited_at: Option<DateTime<Utc>>,
+    body: String,
+    #[serde(rename = "bodyHTML")]
+    body_html: String,
+    author: Option<Author>,
+    category: Option<Category>,
+    labels: Option<Nodes<Label>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Author {
+    login: String,
+    avatar_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Category {
+    slug: String,
+}
+
+#[derive(Deserialize)]
+struct Label {
+    name: String,
+}
+
+impl GraphQlError {
+    fn new(message: String) -> Self {
+        GraphQlError { message }
+    }
+}
+
+impl std::fmt::Display for GraphQlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GitHub API error: {}", self.message)
+    }
+}
+
+impl std::error::Error for GraphQlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+impl Discussion {
+    /// Convert this to a post, with its body converted as described by `formatting`.
+    fn into_post(self, formatting: &FormattingConfig) -> DiscussionPost {
+        let options = formatting.markdown_options(&self.url);
+        let link = DiscussionLink::new(self.url, self.number);
+
+        let (author, avatar_url) = match self.author {
+            Some(author) => (author.login, author.avatar_url),
+            None => ("Unknown Author".to_string(), None),
+        };
+        let labels = match self.labels {
+            Some(labels) => labels.nodes.into_iter().map(|label| label.name).collect(),
+            None => Vec::new(),
+        };
+        let (content, images) = html_to_md_minimal_with_options(&self.body_html, options);
+
+        let mut post = DiscussionPost::new(content.trim().to_string(), author, link)
+            .with_title(self.title)
+            .with_images(images)
+            .with_created_at(self.created_at)
+            .with_labels(labels)
+            .with_source_markdown(self.body);
+
+        if let Some(edited_at) = self.last_edited_at {
+            post = post.with_edited_at(edited_at);
+        }
+        if let Some(avatar_url) = avatar_url {
+            post = post.with_author_avatar_url(avatar_url);
+        }
+        if let Some(category) = self.category {
+            post = post.with_category(category.slug);
+        }
+
+        post
+    }
+}
+
+impl GraphQlSource {
+    /// Create a source for the discussions described by `config`, authenticating with `token`.
+    pub fn new(config: ScraperConfig, token: String) -> Self {
+        let endpoint = if config.host == "github.com" {
+            "https://api.github.com/graphql".to_string()
+        } else {
+            // GitHub Enterprise Server
+            format!("https://{}/api/graphql", config.host)
+        };
+
+        GraphQlSource {
+            links: LinkMatcher::new(&config),
+            config,
+            token,
+            endpoint,
+            client: reqwest::Client::new(),
+            formatting: FormattingConfig::default(),
+            category_id: Mutex::new(None),
+            recent_posts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns this, sending requests to `endpoint` rather than GitHub's.
+    pub fn with_endpoint(mut self, endpoint: String) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Returns this, converting posts' content as described by `formatting`.
+    pub fn with_formatting(mut self, formatting: FormattingConfig) -> Self {
+        self.formatting = formatting;
+        self
+    }
+
+    /// Send `query` to the API, returning the `data` in the response.
+    async fn query<T>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<T, Box<dyn std::error::Error>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let response: Response<T> = self
+            .client
+            .post(&self.endpoint)
+            .bearer_auth(&self.token)
+            .header(reqwest::header::USER_AGENT, "forwarding-bot")
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if let Some(errors) = response.errors {
+            if !errors.is_empty() {
+                let messages: Vec<String> = errors.into_iter().map(|e| e.message).collect();
+                return Err(Box::new(GraphQlError::new(messages.join("; "))));
+            }
+        }
+
+        match response.data {
+            Some(data) => Ok(data),
+            None => Err(Box::new(GraphQlError::new(
+                "Response contained no data".to_string(),
+            ))),
+        }
+    }
+
+    /// Get the node id of the configured discussion category.
+    async fn get_category_id(&self) -> Result<String, Box<dyn std::error::Error>> {
+        if let Some(id) = self.category_id.lock().unwrap().clone() {
+            return Ok(id);
+        }
+
+        let data: RepositoryData<CategoryRepository> = self
+            .query(
+                CATEGORY_ID_QUERY,
+                json!({
+                    "owner": self.config.owner,
+                    "repo": self.config.repo,
+                    "category": self.config.category,
+                }),
+            )
+            .await?;
+
+        let id = match data.repository.and_then(|r| r.discussion_category) {
+            Some(category) => category.id,
+            None => {
+                return Err(Box::new(GraphQlError::new(format!(
+                    "No discussion category {} in {}/{}",
+                    self.config.category, self.config.owner, self.config.repo
+                ))));
+            }
+        };
+
+        *self.category_id.lock().unwrap() = Some(id.clone());
+        Ok(id)
+    }
+
+    /// Fetch the discussions in the category with ids greater than `cursor`, sorted by
+    /// increasing id. Without a `cursor`, only the most recent [PAGE_SIZE] are fetched.
+    pub async fn fetch_posts(
+        &self,
+        cursor: Option<u64>,
+    ) -> Result<Vec<DiscussionPost>, Box<dyn std::error::Error>> {
+        let category_id = self.get_category_id().await?;
+        let mut discussions: Vec<Discussion> = Vec::new();
+        let mut after: Option<String> = None;
+
+        // Discussions are listed newest first, so stop at the first page that reaches `cursor`.
+        loop {
+            let data: RepositoryData<DiscussionsRepository> = self
+                .query(
+                    DISCUSSIONS_QUERY,
+                    json!({
+                        "owner": self.config.owner,
+                        "repo": self.config.repo,
+                        "categoryId": category_id,
+                        "first": PAGE_SIZE,
+                        "after": after,
+                    }),
+                )
+                .await?;
+
+            let page = match data.repository {
+                Some(repository) => repository.discussions,
+                None => {
+                    return Err(Box::new(GraphQlError::new(format!(
+                        "No repository {}/{}",
+                        self.config.owner, self.config.repo
+                    ))));
+                }
+            };
+
+            let reached_cursor = match cursor {
+                Some(cursor) => page
+                    .nodes
+                    .iter()
+                    .any(|discussion| discussion.number <= cursor),
+                None => true,
+            };
+            discussions.extend(page.nodes);
+
+            match page.page_info.end_cursor {
+                Some(end_cursor) if page.page_info.has_next_page && !reached_cursor => {
+                    after = Some(end_cursor);
+                }
+                _ => break,
+            }
+        }
+
+        let mut posts: Vec<DiscussionPost> = discussions
+            .into_iter()
+            .map(|discussion| discussion.into_post(&self.formatting))
+            .collect();
+        posts.sort_by_key(|post| post.get_link().get_id());
+
+        Ok(posts)
+    }
+
+    /// Fetch the discussion numbered `id`.
+    pub async fn fetch_post(&self, id: u64) -> Result<DiscussionPost, Box<dyn std::error::Error>> {
+        let data: RepositoryData<DiscussionRepository> = self
+            .query(
+                DISCUSSION_QUERY,
+                json!({
+                    "owner": self.config.owner,
+                    "repo": self.config.repo,
+                    "number": id,
+                }),
+            )
+            .await?;
+
+        match data.repository.and_then(|r| r.discussion) {
+            Some(discussion) => Ok(discussion.into_post(&self.formatting)),
+            None => Err(Box::new(GraphQlError::new(format!(
+                "No discussion #{} in {}/{}",
+                id, self.config.owner, self.config.repo
+            )))),
+        }
+    }
+}
+
+#[async_trait]
+impl DiscussionSource for GraphQlSource {
+    fn get_source_key(&self) -> String {
+        self.config.get_source_key()
+    }
+
+    fn get_post_to_url(&self) -> String {
+        self.config.get_post_to_url()
+    }
+
+    fn find_links(&self, text: &str) -> Vec<DiscussionLink> {
+        self.links.find_links(text)
+    }
+
+    fn get_link(&self, id: u64) -> DiscussionLink {
+        self.config.get_link(id)
+    }
+
+    async fn list_since(
+        &self,
+        cursor: Option<u64>,
+    ) -> Result<Vec<DiscussionLink>, Box<dyn std::error::Error>> {
+        let posts = self.fetch_posts(cursor).await?;
+        let posts: Vec<DiscussionPost> = posts
+            .into_iter()
+            .filter(|post| cursor.is_none_or(|cursor| post.get_link().get_id() > cursor))
+            .collect();
+        let links = posts.iter().map(|post| post.get_link().clone()).collect();
+
+        let mut recent_posts = self.recent_posts.lock().unwrap();
+        recent_posts.clear();
+        for post in posts {
+            recent_posts.insert(post.get_link().get_id(), post);
+        }
+
+        Ok(links)
+    }
+
+    async fn fetch(
+        &self,
+        link: &DiscussionLink,
+    ) -> Result<DiscussionPost, Box<dyn std::error::Error>> {
+        let cached = self.recent_posts.lock().unwrap().remove(&link.get_id());
+
+        match cached {
+            Some(post) => Ok(post),
+            None => self.fetch_post(link.get_id()).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GraphQlSource;
+    use crate::discussion_source::DiscussionSource;
+    use crate::github_scraper::ScraperConfig;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Start a server that answers each HTTP request with `respond(request body)`.
+    /// Returns the server's URL and a list that each request's headers and body are added to.
+    async fn start_mock_server<F>(respond: F) -> (String, Arc<Mutex<Vec<String>>>)
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let requests_to_fill = requests.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+
+                // Read until we have the headers and the full body.
+                loop {
+                    let count = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..count]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+
+                    if let Some(header_end) = text.find("\r\n\r\n") {
+                        let content_length = text[..header_end]
+                            .lines()
+                            .find_map(|line| {
+                                let (name, value) = line.split_once(':')?;
+                                if name.eq_ignore_ascii_case("content-length") {
+                                    value.trim().parse::<usize>().ok()
+                                } else {
+                                    None
+                                }
+                            })
+                            .unwrap_or(0);
+
+                        if request.len() >= header_end + 4 + content_length || count == 0 {
+                            break;
+                        }
+                    } else if count == 0 {
+                        break;
+                    }
+                }
+
+                let text = String::from_utf8_lossy(&request).to_string();
+                let body = text.split_once("\r\n\r\n").map(|(_, b)| b).unwrap_or("");
+                let response_body = respond(body);
+                requests_to_fill.lock().unwrap().push(text.clone());
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response_body.len(),
+                    response_body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+                socket.shutdown().await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    const DISCUSSION_JSON: &str = r#"{
+        "number": 70000,
+        "title": "Summer internship",
+        "url": "https://github.com/UWAppDev/community/discussions/70000",
+        "createdAt": "2021-09-22T18:04:11Z",
+        "lastEditedAt": null,
+        "body": "Apply **now**!",
+        "bodyHTML": "<p>Apply <strong>now</strong>!</p>",
+        "author": { "login": "personalizedrefrigerator", "avatarUrl": "https://avatars.githubusercontent.com/u/46334387" },
+        "category": { "slug": "opportunities" },
+        "labels": { "nodes": [{ "name": "paid" }, { "name": "remote" }] }
+    }"#;
+
+    fn respond(body: &str) -> String {
+        if body.contains("discussionCategory") {
+            r#"{"data": {"repository": {"discussionCategory": {"id": "DIC_123"}}}}"#.to_string()
+        } else if body.contains("discussions(") {
+            assert!(body.contains("DIC_123"), "Category id not used: {}", body);
+            format!(
+                r#"{{"data": {{"repository": {{"discussions": {{"pageInfo": {{"hasNextPage": false, "endCursor": "c1"}}, "nodes": [{}, {}]}}}}}}}}"#,
+                DISCUSSION_JSON.replace("70000", "70001"),
+                DISCUSSION_JSON
+            )
+        } else {
+            format!(
+                r#"{{"data": {{"repository": {{"discussion": {}}}}}}}"#,
+                DISCUSSION_JSON
+            )
+        }
+    }
+
+    fn config() -> ScraperConfig {
+        ScraperConfig::new("UWAppDev", "community", "opportunities")
+    }
+
+    #[tokio::test]
+    async fn test_fetch_posts_from_mock_server() {
+        let (url, requests) = start_mock_server(respond).await;
+        let source = GraphQlSource::new(config(), "secret".to_string()).with_endpoint(url);
+
+        let posts = source.fetch_posts(None).await.unwrap();
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].get_link().get_id(), 70000);
+        assert_eq!(posts[1].get_link().get_id(), 70001);
+
+        let post = &posts[0];
+        assert_eq!(post.get_title(), Some("Summer internship"));
+        assert_eq!(post.get_author(), "personalizedrefrigerator");
+        assert_eq!(post.get_content(), "Apply **now**!");
+        assert_eq!(post.get_source_markdown(), Some("Apply **now**!"));
+        assert_eq!(post.get_labels(), &["paid", "remote"]);
+        assert_eq!(post.get_category(), Some("opportunities"));
+        assert_eq!(
+            post.get_author_avatar_url(),
+            Some("https://avatars.githubusercontent.com/u/46334387")
+        );
+        assert_eq!(
+            post.get_created_at().unwrap().to_rfc3339(),
+            "2021-09-22T18:04:11+00:00"
+        );
+        assert_eq!(
+            post.get_link().get_url(),
+            "https://github.com/UWAppDev/community/discussions/70000"
+        );
+
+        // The category id should be cached.
+        source.fetch_posts(None).await.unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0]
+            .to_lowercase()
+            .contains("authorization: bearer secret"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_post_from_mock_server() {
+        let (url, _requests) = start_mock_server(respond).await;
+        let source = GraphQlSource::new(config(), "secret".to_string()).with_endpoint(url);
+
+        let post = source.fetch_post(70000).await.unwrap();
+        assert_eq!(post.get_title(), Some("Summer internship"));
+        assert!(!post.is_edited());
+    }
+
+    #[tokio::test]
+    async fn test_list_since_caches_posts() {
+        let (url, requests) = start_mock_server(respond).await;
+        let source = GraphQlSource::new(config(), "secret".to_string()).with_endpoint(url);
+
+        let links = source.list_since(Some(70000)).await.unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].get_id(), 70001);
+
+        // Listed posts shouldn't be requested again.
+        let post = source.fetch(&links[0]).await.unwrap();
+        assert_eq!(post.get_link().get_id(), 70001);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    /// Like [respond], but lists discussions 70000 to 70003 two at a time.
+    fn respond_with_pages(body: &str) -> String {
+        if !body.contains("discussions(") {
+            return respond(body);
+        }
+
+        let (numbers, page_info) = if body.contains(r#""after":"c1""#) {
+            (
+                ["70001", "70000"],
+                r#"{"hasNextPage": false, "endCursor": "c2"}"#,
+            )
+        } else {
+            (
+                ["70003", "70002"],
+                r#"{"hasNextPage": true, "endCursor": "c1"}"#,
+            )
+        };
+        let nodes: Vec<String> = numbers
+            .iter()
+            .map(|number| DISCUSSION_JSON.replace("70000", number))
+            .collect();
+
+        format!(
+            r#"{{"data": {{"repository": {{"discussions": {{"pageInfo": {}, "nodes": [{}]}}}}}}}}"#,
+            page_info,
+            nodes.join(", ")
+        )
+    }
+
+    #[tokio::test]
+    async fn test_list_since_fetches_every_page() {
+        let (url, requests) = start_mock_server(respond_with_pages).await;
+        let source = GraphQlSource::new(config(), "secret".to_string()).with_endpoint(url);
+
+        let links = source.list_since(Some(70000)).await.unwrap();
+        let ids: Vec<u64> = links.iter().map(|link| link.get_id()).collect();
+        assert_eq!(ids, [70001, 70002, 70003]);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        // Pages older than the cursor aren't requested.
+        let links = source.list_since(Some(70002)).await.unwrap();
+        let ids: Vec<u64> = links.iter().map(|link| link.get_id()).collect();
+        assert_eq!(ids, [70003]);
+        assert_eq!(requests.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_graphql_errors_are_returned() {
+        let (url, _requests) = start_mock_server(|_body| {
+            r#"{"data": null, "errors": [{"message": "Could not resolve to a Repository"}]}"#
+                .to_string()
+        })
+        .await;
+        let source = GraphQlSource::new(config(), "secret".to_string()).with_endpoint(url);
+
+        let error = source.fetch_posts(None).await.unwrap_err();
+        assert!(
+            error.to_string().contains("Could not resolve"),
+            "Unexpected error: {}",
+            error
+        );
+    }
+}
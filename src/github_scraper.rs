@@ -1,42 +1,141 @@
 //! Searches for discussions on GitHub marked with "opportunity"
 
-use lazy_static::lazy_static;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use regex::Regex;
+use serde::Deserialize;
 use std::collections::BTreeSet;
 
 use select::document::Document;
-use select::predicate::{Attr, Class};
+use select::node::Node;
+use select::predicate::{Attr, Class, Name};
+
+use crate::config::{FormattingConfig, SourceApi};
+use crate::discussion_source::DiscussionSource;
+use crate::html_walker::html_to_md_minimal_with_options;
+
+/// Describes which GitHub discussion category opportunities are taken from.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScraperConfig {
+    /// The user or organization that owns [ScraperConfig::repo].
+    pub owner: String,
+
+    /// The name of the repository whose discussions are scraped.
+    pub repo: String,
+
+    /// The slug of the discussion category (as it appears in the category's URL).
+    #[serde(default = "default_category")]
+    pub category: String,
+
+    /// The GitHub host, without a scheme. For example, `github.com`.
+    #[serde(default = "default_host")]
+    pub host: String,
+
+    /// How discussions are fetched.
+    #[serde(default)]
+    pub api: SourceApi,
+}
 
-use crate::html_walker::html_to_md_minimal;
+fn default_category() -> String {
+    "opportunities".to_string()
+}
 
-// When production-ready, replace with "/UWAppDev/community/discussions"
-macro_rules! DISCUSSIONS_BASE_URL {
-    () => {
-        "UWAppDev/opportunities-forwarding-bot/discussions/"
-    };
+fn default_host() -> String {
+    "github.com".to_string()
 }
-macro_rules! OPPORTUNITIES_LIST_URL {
-    () => {
-        concat!(
-            "https://github.com/",
-            DISCUSSIONS_BASE_URL!(),
-            "categories/opportunities/"
-        )
-    };
+
+impl Default for ScraperConfig {
+    fn default() -> Self {
+        // When production-ready, replace with UWAppDev/community
+        ScraperConfig::new("UWAppDev", "opportunities-forwarding-bot", "opportunities")
+    }
 }
-macro_rules! DISCUSSION_LINK_REGEX {
-    () => {
-        concat!(r"/", DISCUSSIONS_BASE_URL!(), r"[/]*(?P<id>\d+)");
-    };
+
+impl ScraperConfig {
+    /// Create a config for the `category` discussion category of `owner/repo` on github.com.
+    pub fn new(owner: &str, repo: &str, category: &str) -> Self {
+        ScraperConfig {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            category: category.to_string(),
+            host: default_host(),
+            api: SourceApi::Html,
+        }
+    }
+
+    /// Get the path (relative to the host) of the repository's discussions.
+    /// For example, `/UWAppDev/community/discussions/`.
+    pub fn discussions_base_path(&self) -> String {
+        format!("/{}/{}/discussions/", self.owner, self.repo)
+    }
+
+    /// Get the full URL to the list of discussions in the category.
+    ///
+    /// For example:
+    /// ```
+    /// # use forwarding_bot::github_scraper::ScraperConfig;
+    /// let config = ScraperConfig::new("UWAppDev", "community", "opportunities");
+    /// assert_eq!(
+    ///     config.get_list_url(),
+    ///     "https://github.com/UWAppDev/community/discussions/categories/opportunities/"
+    /// );
+    /// ```
+    pub fn get_list_url(&self) -> String {
+        format!(
+            "https://{}{}categories/{}/",
+            self.host,
+            self.discussions_base_path(),
+            self.category
+        )
+    }
+
+    /// Where _users_ should post new opportunities.
+    pub fn get_post_to_url(&self) -> String {
+        self.get_list_url()
+    }
+
+    /// Get a link to the discussion numbered `id` in the repository.
+    ///
+    /// For example:
+    /// ```
+    /// # use forwarding_bot::github_scraper::ScraperConfig;
+    /// let config = ScraperConfig::new("UWAppDev", "community", "opportunities");
+    /// assert_eq!(
+    ///     config.get_link(5).get_url(),
+    ///     "https://github.com/UWAppDev/community/discussions/5"
+    /// );
+    /// ```
+    pub fn get_link(&self, id: u64) -> DiscussionLink {
+        let url = format!(
+            "https://{}{}{}",
+            self.host,
+            self.discussions_base_path(),
+            id
+        );
+        DiscussionLink::new(url, id)
+    }
+
+    /// Get a string that identifies the repository discussions are taken from,
+    /// for remembering which have been forwarded. For example, `github.com/UWAppDev/community`.
+    pub fn get_source_key(&self) -> String {
+        format!("{}/{}/{}", self.host, self.owner, self.repo)
+    }
 }
 
-/// Where _users_ should post new opportunities.
-pub const OPPORTUNITIES_POST_TO_URL: &str = OPPORTUNITIES_LIST_URL!();
+/// Finds links to discussions in one repository. Build this once and reuse it,
+/// rather than compiling its pattern for every search.
+#[derive(Clone, Debug)]
+pub struct LinkMatcher {
+    host: String,
+    regex: Regex,
+}
 
 #[derive(Clone, Debug)]
 pub struct DiscussionLink {
     content: String,
-    id: u16,
+    host: String,
+    id: u64,
 }
 
 #[derive(Clone, Debug)]
@@ -44,44 +143,72 @@ pub struct DiscussionPost {
     content: String,
     author: String,
     url: DiscussionLink,
+    title: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    edited_at: Option<DateTime<Utc>>,
+    labels: Vec<String>,
+    source_markdown: Option<String>,
+    author_avatar_url: Option<String>,
+    category: Option<String>,
+    images: Vec<String>,
 }
 
 #[derive(Debug)]
 struct PostNotFoundError;
 
-impl DiscussionLink {
-    /// Create a link. Assumes `full_link_text` points to a valid discussion.
-    pub fn new(full_link_text: String, id: u16) -> DiscussionLink {
-        DiscussionLink {
-            content: full_link_text,
-            id,
+/// A [DiscussionSource] that scrapes discussions from GitHub's HTML.
+pub struct HtmlScraperSource {
+    config: ScraperConfig,
+    links: LinkMatcher,
+    formatting: FormattingConfig,
+}
+
+impl LinkMatcher {
+    /// Create a matcher for links to discussions in `config`'s repository.
+    pub fn new(config: &ScraperConfig) -> Self {
+        // Links may be relative, or name a host (with or without a scheme). The
+        // host is captured so that links to a same-named repository elsewhere
+        // can be skipped.
+        let pattern = format!(
+            r"(?:(?:https?://)?(?P<host>[A-Za-z0-9.-]+(?::\d+)?))?/{}/{}/discussions/[/]*(?P<id>\d+)",
+            regex::escape(&config.owner),
+            regex::escape(&config.repo)
+        );
+
+        LinkMatcher {
+            host: config.host.clone(),
+            regex: Regex::new(&pattern)
+                .expect("Escaped owner and repository should form a valid regex"),
         }
     }
 
-    /// Extract all links to discussion posts from this' remote repository.
-    pub async fn fetch() -> Result<Vec<DiscussionLink>, Box<dyn std::error::Error>> {
-        let html = reqwest::get(OPPORTUNITIES_LIST_URL!())
-            .await?
-            .text()
-            .await?;
-
-        Ok(Self::pull_from(&html))
+    /// Returns true iff `host` (as written in a link) refers to [LinkMatcher::host].
+    fn is_own_host(&self, host: &str) -> bool {
+        let host = host.strip_prefix("www.").unwrap_or(host);
+        host.eq_ignore_ascii_case(&self.host)
     }
 
-    /// Pull and return all links to discussion posts from `text`.
-    pub fn pull_from(text: &str) -> Vec<DiscussionLink> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(DISCUSSION_LINK_REGEX!()).unwrap();
-        }
-        let mut seen_ids: BTreeSet<u16> = BTreeSet::new();
+    /// Pull and return all links to discussion posts in the repository from `text`.
+    pub fn find_links(&self, text: &str) -> Vec<DiscussionLink> {
+        let mut seen_ids: BTreeSet<u64> = BTreeSet::new();
 
-        let mut res: Vec<DiscussionLink> = RE
+        let mut res: Vec<DiscussionLink> = self
+            .regex
             .captures_iter(text)
-            .map(|captures| {
+            .filter(|captures| {
+                captures
+                    .name("host")
+                    .is_none_or(|host| self.is_own_host(host.as_str()))
+            })
+            .filter_map(|captures| {
                 let full_link: String = captures[0].into();
-                let id: u16 = captures["id"].parse().unwrap();
 
-                DiscussionLink::new(full_link, id)
+                // Skip links with ids too large to represent, rather than panicking.
+                let id: u64 = captures["id"].parse().ok()?;
+
+                let mut link = DiscussionLink::new(full_link, id);
+                link.host = self.host.clone();
+                Some(link)
             })
             .filter(|link| {
                 if seen_ids.contains(&link.get_id()) {
@@ -92,13 +219,42 @@ impl DiscussionLink {
                 true
             })
             .collect();
-        res.sort_by(|a, b| a.id.cmp(&b.id));
+        res.sort_by_key(|link| link.id);
 
         res
     }
+}
+
+impl DiscussionLink {
+    /// Create a link. Assumes `full_link_text` points to a valid discussion.
+    pub fn new(full_link_text: String, id: u64) -> DiscussionLink {
+        DiscussionLink {
+            content: full_link_text,
+            host: "github.com".to_string(),
+            id,
+        }
+    }
+
+    /// Extract all links to discussion posts from the category described by `config`,
+    /// using `links` to find them.
+    pub async fn fetch(
+        config: &ScraperConfig,
+        links: &LinkMatcher,
+    ) -> Result<Vec<DiscussionLink>, Box<dyn std::error::Error>> {
+        let html = reqwest::get(config.get_list_url()).await?.text().await?;
+
+        Ok(links.find_links(&html))
+    }
+
+    /// Pull and return all links to discussion posts in `config`'s repository from `text`.
+    ///
+    /// This builds a new [LinkMatcher] each time; keep one instead when searching repeatedly.
+    pub fn pull_from(config: &ScraperConfig, text: &str) -> Vec<DiscussionLink> {
+        LinkMatcher::new(config).find_links(text)
+    }
 
     /// Get the id associated with the link.
-    pub fn get_id(&self) -> u16 {
+    pub fn get_id(&self) -> u64 {
         self.id
     }
 
@@ -114,12 +270,15 @@ impl DiscussionLink {
     pub fn get_url(&self) -> String {
         if self.content.starts_with("http") {
             self.content.clone()
-        } else if self.content.starts_with("github") || self.content.starts_with("www.") {
+        } else if self.content.starts_with(&self.host[..])
+            || self.content.starts_with("github")
+            || self.content.starts_with("www.")
+        {
             format!("https://{}", self.content)
         } else if self.content.starts_with('/') {
-            format!("https://github.com{}", self.content)
+            format!("https://{}{}", self.host, self.content)
         } else {
-            format!("https://github.com/{}", self.content)
+            format!("https://{}/{}", self.host, self.content)
         }
     }
 }
@@ -142,42 +301,96 @@ impl std::error::Error for PostNotFoundError {
 impl DiscussionPost {
     /// Creates a new discussion post with `content` and location `link`.
     /// This does not fetch or verify the `content` using `link`.
-    fn new(content: String, author: String, link: DiscussionLink) -> DiscussionPost {
+    pub fn new(content: String, author: String, link: DiscussionLink) -> DiscussionPost {
         DiscussionPost {
             content,
             author,
             url: link,
+            title: None,
+            created_at: None,
+            edited_at: None,
+            labels: Vec::new(),
+            source_markdown: None,
+            author_avatar_url: None,
+            category: None,
+            images: Vec::new(),
         }
     }
 
-    /// Fetches all applicable discussion posts from this project's GitHub.
+    /// Returns this with the given title.
+    pub fn with_title(mut self, title: String) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    /// Returns this, marked as created at `created_at`.
+    pub fn with_created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    /// Returns this, marked as last edited at `edited_at`.
+    pub fn with_edited_at(mut self, edited_at: DateTime<Utc>) -> Self {
+        self.edited_at = Some(edited_at);
+        self
+    }
+
+    /// Returns this with the given labels.
+    pub fn with_labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Returns this with `markdown` as the markdown the author wrote.
+    pub fn with_source_markdown(mut self, markdown: String) -> Self {
+        self.source_markdown = Some(markdown);
+        self
+    }
+
+    /// Returns this, with `url` pointing to the author's avatar.
+    pub fn with_author_avatar_url(mut self, url: String) -> Self {
+        self.author_avatar_url = Some(url);
+        self
+    }
+
+    /// Returns this, marked as belonging to the discussion category with slug `category`.
+    pub fn with_category(mut self, category: String) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    /// Returns this with `images` as the (absolute) URLs of the images in its content.
+    pub fn with_images(mut self, images: Vec<String>) -> Self {
+        self.images = images;
+        self
+    }
+
+    /// Fetches all applicable discussion posts from this project's GitHub, converting
+    /// their content as described by `formatting`.
     /// As this involves network communication, errors are possible.
     pub async fn fetch_from(
         link: DiscussionLink,
+        formatting: &FormattingConfig,
     ) -> Result<DiscussionPost, Box<dyn std::error::Error>> {
         let html = reqwest::get(link.get_url()).await?.text().await?;
 
-        Self::pull_from(link, &html)
+        Self::pull_from(link, &html, formatting)
     }
 
     /// Create a DiscussionPost from given `html` that has been fetched from `link`.
     fn pull_from(
         link: DiscussionLink,
         html: &str,
+        formatting: &FormattingConfig,
     ) -> Result<DiscussionPost, Box<dyn std::error::Error>> {
         let document = Document::from(html);
-        let first_comment = document.find(Class("unminimized-comment")).next();
-
-        if None == first_comment {
-            return Err(Box::new(PostNotFoundError));
-        }
-        let first_comment = first_comment.unwrap();
+        let first_comment = match document.find(Class("unminimized-comment")).next() {
+            Some(comment) => comment,
+            None => return Err(Box::new(PostNotFoundError)),
+        };
 
         let author = first_comment
-            .find(select::predicate::And(
-                Class("author"),
-                select::predicate::Name("a"),
-            ))
+            .find(select::predicate::And(Class("author"), Name("a")))
             .next();
         let content = first_comment
             .find(select::predicate::And(
@@ -191,18 +404,94 @@ impl DiscussionPost {
             None => "Unknown Author".to_string(),
         };
 
-        let content = match content {
+        let (content, images) = match content {
             Some(node) => {
-                let post_html = node.html();
-                html_to_md_minimal(&post_html[..])
+                // The post's body is wrapped in a layout table, which shouldn't be
+                // converted to a markdown table.
+                let body = node.find(Class("comment-body")).next().unwrap_or(node);
+                let post_html: String = body.children().map(|child| child.html()).collect();
+                let options = formatting.markdown_options(&link.get_url());
+                html_to_md_minimal_with_options(&post_html[..], options)
             }
-            None => "Unable to find content for this post!!!".to_string(),
+            None => (
+                "Unable to find content for this post!!!".to_string(),
+                Vec::new(),
+            ),
         };
 
         let author = author.trim().to_string();
         let content = content.trim().to_string();
+        let mut post = DiscussionPost::new(content, author, link).with_images(images);
+
+        if let Some(title) = Self::pull_title_from(&document) {
+            post = post.with_title(title);
+        }
+
+        let avatar = first_comment
+            .find(select::predicate::And(Class("avatar"), Name("img")))
+            .next()
+            .and_then(|node| node.attr("src"));
+        if let Some(avatar) = avatar {
+            post = post.with_author_avatar_url(avatar.to_string());
+        }
+
+        // Labels are listed in the sidebar.
+        let labels: Vec<String> = document
+            .find(Class("IssueLabel"))
+            .map(|node| node.text().trim().to_string())
+            .filter(|label| !label.is_empty())
+            .collect();
+        post = post.with_labels(labels);
+
+        // The comment's permalink shows when it was posted.
+        let created_at = first_comment
+            .find(Class("js-timestamp"))
+            .next()
+            .and_then(|node| Self::pull_latest_time_from(&node));
+        if let Some(created_at) = created_at {
+            post = post.with_created_at(created_at);
+        }
+
+        // If the comment has been edited, its edit history lists when.
+        let edited_at = first_comment
+            .find(Class("js-comment-edit-history"))
+            .next()
+            .and_then(|node| Self::pull_latest_time_from(&node));
+        if let Some(edited_at) = edited_at {
+            post = post.with_edited_at(edited_at);
+        }
+
+        Ok(post)
+    }
+
+    /// Get the latest time given by a `relative-time` element within `node`.
+    fn pull_latest_time_from(node: &Node) -> Option<DateTime<Utc>> {
+        node.find(Name("relative-time"))
+            .filter_map(|time| time.attr("datetime"))
+            .filter_map(|datetime| DateTime::parse_from_rfc3339(datetime).ok())
+            .map(|datetime| datetime.with_timezone(&Utc))
+            .max()
+    }
+
+    /// Find the title of the discussion shown in `document`.
+    fn pull_title_from(document: &Document) -> Option<String> {
+        // The header shows the title in a js-issue-title. If GitHub has changed
+        // its markup, fall back to the page's title, which is of the form
+        // "Title · Discussion #5 · owner/repo · GitHub".
+        let title = match document.find(Class("js-issue-title")).next() {
+            Some(node) => node.text(),
+            None => {
+                let page_title = document.find(Name("title")).next()?.text();
+                page_title.split(" · ").next()?.to_string()
+            }
+        };
 
-        Ok(DiscussionPost::new(content, author, link))
+        let title = title.trim();
+        if title.is_empty() {
+            None
+        } else {
+            Some(title.to_string())
+        }
     }
 
     /// Get the markdown content of this post.
@@ -219,32 +508,199 @@ impl DiscussionPost {
     pub fn get_link(&self) -> &DiscussionLink {
         &self.url
     }
+
+    /// Get the title of the discussion, if known.
+    pub fn get_title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Get when the discussion was created, if known.
+    pub fn get_created_at(&self) -> Option<DateTime<Utc>> {
+        self.created_at
+    }
+
+    /// Get when the discussion was last edited, or `None` if it hasn't been
+    /// (or this is unknown).
+    pub fn get_edited_at(&self) -> Option<DateTime<Utc>> {
+        self.edited_at
+    }
+
+    /// Returns true iff the discussion has been edited since it was created.
+    pub fn is_edited(&self) -> bool {
+        match (self.created_at, self.edited_at) {
+            (Some(created_at), Some(edited_at)) => edited_at > created_at,
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Get the names of the labels applied to the discussion.
+    pub fn get_labels(&self) -> &[String] {
+        &self.labels[..]
+    }
+
+    /// Get the URL of the author's avatar, if known.
+    pub fn get_author_avatar_url(&self) -> Option<&str> {
+        self.author_avatar_url.as_deref()
+    }
+
+    /// Get the slug of the discussion category this belongs to, if known.
+    pub fn get_category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    /// Get the URLs of the images in the discussion's content, in order.
+    pub fn get_images(&self) -> &[String] {
+        &self.images[..]
+    }
+
+    /// Get the markdown the author wrote, if known. Unlike [DiscussionPost::get_content],
+    /// this hasn't been converted for display in Discord.
+    pub fn get_source_markdown(&self) -> Option<&str> {
+        self.source_markdown.as_deref()
+    }
+}
+
+impl HtmlScraperSource {
+    pub fn new(config: ScraperConfig) -> Self {
+        HtmlScraperSource {
+            links: LinkMatcher::new(&config),
+            config,
+            formatting: FormattingConfig::default(),
+        }
+    }
+
+    /// Returns this, converting posts' content as described by `formatting`.
+    pub fn with_formatting(mut self, formatting: FormattingConfig) -> Self {
+        self.formatting = formatting;
+        self
+    }
+}
+
+#[async_trait]
+impl DiscussionSource for HtmlScraperSource {
+    fn get_source_key(&self) -> String {
+        self.config.get_source_key()
+    }
+
+    fn get_post_to_url(&self) -> String {
+        self.config.get_post_to_url()
+    }
+
+    fn find_links(&self, text: &str) -> Vec<DiscussionLink> {
+        self.links.find_links(text)
+    }
+
+    fn get_link(&self, id: u64) -> DiscussionLink {
+        self.config.get_link(id)
+    }
+
+    async fn list_since(
+        &self,
+        cursor: Option<u64>,
+    ) -> Result<Vec<DiscussionLink>, Box<dyn std::error::Error>> {
+        let links = DiscussionLink::fetch(&self.config, &self.links).await?;
+
+        Ok(links
+            .into_iter()
+            .filter(|link| cursor.is_none_or(|cursor| link.get_id() > cursor))
+            .collect())
+    }
+
+    async fn fetch(
+        &self,
+        link: &DiscussionLink,
+    ) -> Result<DiscussionPost, Box<dyn std::error::Error>> {
+        let post = DiscussionPost::fetch_from(link.clone(), &self.formatting).await?;
+
+        Ok(post.with_category(self.config.category.clone()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{DiscussionLink, DiscussionPost};
+    use super::{DiscussionLink, DiscussionPost, FormattingConfig, LinkMatcher, ScraperConfig};
 
     #[test]
     fn test_link_scrape_simple() {
-        let source = format!(
-            "/{}123, /{}/0",
-            DISCUSSIONS_BASE_URL!(),
-            DISCUSSIONS_BASE_URL!()
-        );
-        let links = DiscussionLink::pull_from(&source);
+        let config = ScraperConfig::default();
+        let base = config.discussions_base_path();
+        let source = format!("{}123, {}/0", base, base);
+        let links = DiscussionLink::pull_from(&config, &source);
 
         assert_eq!(links.len(), 2, "Ensure we find two links in {}", source);
         assert_eq!(links[1].get_id(), 123);
         assert_eq!(links[1].id, 123);
         assert_eq!(links[0].id, 0);
-        assert_eq!(links[1].content, format!("/{}123", DISCUSSIONS_BASE_URL!()));
+        assert_eq!(links[1].content, format!("{}123", base));
+    }
+
+    #[test]
+    fn test_link_scrape_other_repository() {
+        let mut config = ScraperConfig::new("UWAppDev", "community", "opportunities");
+        config.host = "github.example.com".to_string();
+        let source = "/UWAppDev/community/discussions/7 /UWAppDev/opportunities-forwarding-bot/discussions/8";
+        let links = DiscussionLink::pull_from(&config, source);
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].get_id(), 7);
+        assert_eq!(
+            links[0].get_url(),
+            "https://github.example.com/UWAppDev/community/discussions/7"
+        );
+        assert_eq!(
+            config.get_list_url(),
+            "https://github.example.com/UWAppDev/community/discussions/categories/opportunities/"
+        );
+    }
+
+    #[test]
+    fn test_link_scrape_other_host() {
+        let mut config = ScraperConfig::new("UWAppDev", "community", "opportunities");
+        config.host = "github.example.com".to_string();
+        let matcher = LinkMatcher::new(&config);
+        let source = "https://github.com/UWAppDev/community/discussions/1, \
+            github.com/UWAppDev/community/discussions/2, \
+            https://github.example.com/UWAppDev/community/discussions/3, \
+            www.github.example.com/UWAppDev/community/discussions/4, \
+            <a href=\"/UWAppDev/community/discussions/5\">";
+        let links = matcher.find_links(source);
+
+        let ids: Vec<u64> = links.iter().map(|link| link.get_id()).collect();
+        assert_eq!(ids, [3, 4, 5]);
+        assert_eq!(
+            links[0].get_url(),
+            "https://github.example.com/UWAppDev/community/discussions/3"
+        );
+        assert_eq!(
+            links[1].get_url(),
+            "https://www.github.example.com/UWAppDev/community/discussions/4"
+        );
+        assert_eq!(
+            links[2].get_url(),
+            "https://github.example.com/UWAppDev/community/discussions/5"
+        );
+    }
+
+    #[test]
+    fn test_link_scrape_large_ids() {
+        let config = ScraperConfig::default();
+        let base = config.discussions_base_path();
+        let source = format!(
+            "{}65536, {}99999999999999999999999, {}18446744073709551615",
+            base, base, base
+        );
+        let links = DiscussionLink::pull_from(&config, &source);
+
+        assert_eq!(links.len(), 2, "Ensure the out-of-range link is skipped");
+        assert_eq!(links[0].get_id(), 65536);
+        assert_eq!(links[1].get_id(), u64::MAX);
     }
 
     #[test]
     fn test_link_scrape_github() {
         let source = include_str!("../res/tests/ghub_opportunities_list_snapshot.html");
-        let links = DiscussionLink::pull_from(&source);
+        let links = DiscussionLink::pull_from(&ScraperConfig::default(), source);
 
         assert_eq!(links.len(), 4, "Ensure we find three links in our source. Three discussions links and one 'welcome' link.");
         assert_eq!(links[1].get_id(), 3);
@@ -272,15 +728,68 @@ mod tests {
         let post = DiscussionPost::pull_from(
             link,
             include_str!("../res/tests/ghub_opportunities_post_snapshot.html"),
+            &FormattingConfig::default(),
         )
         .unwrap();
         assert_eq!(post.get_author(), "personalizedrefrigerator");
+        assert_eq!(post.get_title(), Some("Yet another test."));
+        assert_eq!(
+            post.get_created_at().map(|time| time.to_rfc3339()),
+            Some("2021-09-22T22:13:19+00:00".to_string())
+        );
+        assert_eq!(post.get_edited_at(), None);
+        assert!(!post.is_edited());
+        assert_eq!(
+            post.get_author_avatar_url(),
+            Some("https://avatars.githubusercontent.com/u/46334387?s=60&v=4")
+        );
+        assert!(post.get_labels().is_empty());
         assert_eq!(
             post.get_content(),
             "This is an opportunity to test the `opportunities-forwarding-bot`!"
         );
     }
 
+    #[test]
+    fn test_discussion_title_from_page_title() {
+        let link = DiscussionLink::new("/UWAppDev/community/discussions/9".to_string(), 9);
+        let html = r#"<html><head><title>Paid internship · Discussion #9 · UWAppDev/community · GitHub</title></head>
+<body><div class="unminimized-comment"><a class="author">someone</a></div></body></html>"#;
+        let post = DiscussionPost::pull_from(link, html, &FormattingConfig::default()).unwrap();
+
+        assert_eq!(post.get_title(), Some("Paid internship"));
+        assert_eq!(post.get_created_at(), None);
+    }
+
+    #[test]
+    fn test_discussion_edit_time() {
+        let link = DiscussionLink::new("/UWAppDev/community/discussions/9".to_string(), 9);
+        let html = r#"<div class="unminimized-comment">
+<a class="author">someone</a>
+<img class="avatar" src="https://avatars.githubusercontent.com/u/1" />
+<span class="js-comment-edit-history"><details><summary>edited</summary>
+  <relative-time datetime="2021-09-23T10:00:00Z">Sep 23</relative-time>
+  <relative-time datetime="2021-09-24T10:00:00Z">Sep 24</relative-time>
+</details></span>
+<a class="js-timestamp"><relative-time datetime="2021-09-22T22:13:19Z">Sep 22</relative-time></a>
+</div>
+<div class="discussion-sidebar-item">
+<a class="IssueLabel"> paid </a><a class="IssueLabel">remote</a>
+</div>"#;
+        let post = DiscussionPost::pull_from(link, html, &FormattingConfig::default()).unwrap();
+
+        assert_eq!(
+            post.get_edited_at().map(|time| time.to_rfc3339()),
+            Some("2021-09-24T10:00:00+00:00".to_string())
+        );
+        assert!(post.is_edited());
+        assert_eq!(
+            post.get_author_avatar_url(),
+            Some("https://avatars.githubusercontent.com/u/1")
+        );
+        assert_eq!(post.get_labels(), &["paid", "remote"]);
+    }
+
     // tokio::test because we're doing a test of an async function
     #[tokio::test]
     async fn test_discussion_post_fetch_from_internet() {
@@ -288,7 +797,7 @@ mod tests {
             "https://github.com/UWAppDev/opportunities-forwarding-bot/discussions/3".to_string(),
             3,
         );
-        let post = DiscussionPost::fetch_from(link)
+        let post = DiscussionPost::fetch_from(link, &FormattingConfig::default())
             .await
             .expect("Unable to fetch remote discussion post!");
         assert_eq!(post.get_author(), "personalizedrefrigerator");
@@ -1,36 +1,86 @@
 //! Utility functions for walking through a parsed HTML tree.
 
+use reqwest::Url;
 use select::document::Document;
 use select::node::Node;
 use select::predicate::*;
+use serde::Deserialize;
 
-/// Options for what to output.
+use crate::message_split::shorten_message;
+use crate::renderer::{Alignment, DiscordRenderer, MarkdownRenderer, PlainTextRenderer, Renderer};
+
+/// Options for what to output. How content is formatted is up to
+/// the walker's [Renderer].
+#[derive(Default)]
 pub struct MarkdownOptions {
-    /// True iff the output markdown should use bold (**)
-    /// text to represent HTML headers.
-    pub use_bold_for_headers: bool,
+    /// The URL of the page the HTML is from. Relative links and images are
+    /// resolved against it (or `https://github.com`, if `None`).
+    pub base_url: Option<String>,
+
+    /// How collapsible `details` sections are output.
+    pub details: DetailsStyle,
+
+    /// The content of `details` sections longer than this many characters is cut short.
+    pub details_limit: Option<usize>,
+
+    /// Where the rest of a shortened `details` section can be read (e.g. the post on GitHub).
+    pub read_more_url: Option<String>,
 }
 
+/// How `details` sections (`<details><summary>...</summary>...</details>`) are output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetailsStyle {
+    /// The summary in bold, followed by the content.
+    #[default]
+    Expanded,
+
+    /// The summary in bold, followed by the content as a spoiler (if the renderer
+    /// supports them). Sections that are open by default are expanded.
+    Spoiler,
+}
+
+/// Shown in place of a `details` section's missing summary, as by browsers.
+const DEFAULT_SUMMARY: &str = "Details";
+
+/// Where relative URLs are resolved from, by default.
+const GITHUB_URL: &str = "https://github.com";
+
 pub struct MarkdownWalker {
     buffer: Vec<String>,
     options: MarkdownOptions,
-}
+    renderer: Box<dyn Renderer>,
 
-impl Default for MarkdownOptions {
-    fn default() -> Self {
-        MarkdownOptions {
-            use_bold_for_headers: false,
-        }
-    }
+    /// Absolute URLs of the images visited so far, in order.
+    images: Vec<String>,
+
+    /// How many `code` or `pre` elements we're in. Text in them isn't escaped.
+    code_depth: usize,
+
+    /// True iff we're in a code block, where whitespace is significant.
+    in_code_block: bool,
+
+    /// When rendering part of the output separately, the character before it.
+    outer_last_char: Option<char>,
 }
 
 impl MarkdownWalker {
-    /// Get an empty [MarkdownWalker].
-    /// This walker can then walk the DOM via [walk].
+    /// Get an empty [MarkdownWalker] that outputs GitHub-flavored markdown.
+    /// This walker can then walk the DOM via [MarkdownWalker::walk].
     pub fn new() -> Self {
+        Self::with_renderer(Box::new(MarkdownRenderer))
+    }
+
+    /// Get an empty [MarkdownWalker] that formats its output with `renderer`.
+    pub fn with_renderer(renderer: Box<dyn Renderer>) -> Self {
         MarkdownWalker {
             buffer: Vec::new(),
             options: Default::default(),
+            renderer,
+            images: Vec::new(),
+            code_depth: 0,
+            in_code_block: false,
+            outer_last_char: None,
         }
     }
 
@@ -45,6 +95,39 @@ impl MarkdownWalker {
         }
     }
 
+    /// Walk `node`'s children, returning their output rather than adding it.
+    /// The children are rendered as if starting a new line.
+    fn render_children(&mut self, node: &Node) -> String {
+        self.render_children_after(node, None)
+    }
+
+    /// Like [MarkdownWalker::render_children], but the children are rendered as
+    /// if following the current output (e.g. for the content of links).
+    fn render_inline(&mut self, node: &Node) -> String {
+        let last_char = self.get_last_char();
+        self.render_children_after(node, last_char)
+    }
+
+    /// Render `node`'s children as if following `last_char` (or at the start of
+    /// the output, if `None`).
+    fn render_children_after(&mut self, node: &Node, last_char: Option<char>) -> String {
+        let outer_buffer = std::mem::take(&mut self.buffer);
+        let outer_last_char = std::mem::replace(&mut self.outer_last_char, last_char);
+        self.visit_children(node);
+        self.outer_last_char = outer_last_char;
+        let rendered = std::mem::replace(&mut self.buffer, outer_buffer);
+
+        rendered.join("")
+    }
+
+    /// Get the last character output, if any.
+    fn get_last_char(&self) -> Option<char> {
+        match self.buffer.iter().rev().find(|text| !text.is_empty()) {
+            Some(text) => text.chars().last(),
+            None => self.outer_last_char,
+        }
+    }
+
     /// Adds the given str to the output.
     fn add<T>(&mut self, text: T)
     where
@@ -53,100 +136,440 @@ impl MarkdownWalker {
         self.buffer.push(String::from(text));
     }
 
+    /// Render the children of `node`, which contain code.
+    fn render_code(&mut self, node: &Node) -> String {
+        self.code_depth += 1;
+        let code = self.render_children(node);
+        self.code_depth -= 1;
+
+        code
+    }
+
+    /// Render the children of `node` as plain text, without any formatting.
+    fn render_plain_text(&mut self, node: &Node) -> String {
+        let renderer = std::mem::replace(&mut self.renderer, Box::new(PlainTextRenderer));
+        let text = self.render_children(node);
+        self.renderer = renderer;
+
+        text
+    }
+
+    /// Render the children of `node` and format them with `format`
+    /// (e.g. [Renderer::strong]). Whitespace around the content is kept
+    /// outside of the formatting, where it can't break it.
+    fn visit_inline<F>(&mut self, node: &Node, format: F)
+    where
+        F: Fn(&dyn Renderer, &str) -> String,
+    {
+        let content = self.render_inline(node);
+        let trimmed = content.trim();
+        if trimmed.is_empty() {
+            self.add(content);
+            return;
+        }
+
+        let start = &content[..content.len() - content.trim_start().len()];
+        let end = &content[content.trim_end().len()..];
+        let formatted = format(self.renderer.as_ref(), trimmed);
+        self.add(format!("{}{}{}", start, formatted, end));
+    }
+
+    fn visit_inline_code(&mut self, node: &Node) {
+        let code = self.render_code(node);
+        let formatted = self.renderer.inline_code(&code);
+
+        self.add(formatted);
+    }
+
+    fn visit_code_block(&mut self, node: &Node) {
+        let language = get_code_language(node).unwrap_or_default();
+        self.in_code_block = true;
+        let code = self.render_code(node);
+        self.in_code_block = false;
+        let code_block = self
+            .renderer
+            .code_block(&language, code.trim_end_matches('\n'));
+
+        self.start_paragraph();
+        self.add(code_block);
+        self.add("\n");
+    }
+
+    /// Returns true iff the next output will be at the start of a line.
+    fn is_at_line_start(&self) -> bool {
+        self.get_last_char().is_none_or(|c| c == '\n')
+    }
+
+    /// Ensure that the next output starts on a new line.
+    fn start_line(&mut self) {
+        let last = self.buffer.iter().rev().find(|text| !text.is_empty());
+
+        if last.is_some_and(|text| !text.ends_with('\n')) {
+            self.add("\n");
+        }
+    }
+
+    /// Ensure that the next output is separated from what came before by a blank line.
+    fn start_paragraph(&mut self) {
+        let output = self.buffer.concat();
+
+        if !output.trim().is_empty() && !output.ends_with("\n\n") {
+            self.add(if output.ends_with('\n') { "\n" } else { "\n\n" });
+        }
+    }
+
     fn visit_header(&mut self, node: &Node, level: u8) {
+        self.start_paragraph();
+
+        let content = self.render_inline(node);
+        let header = self.renderer.header(level, content.trim());
+        self.add(header);
         self.add("\n");
+    }
+
+    fn visit_list(&mut self, node: &Node, ordered: bool) {
+        let start = node
+            .attr("start")
+            .and_then(|start| start.trim().parse::<i64>().ok())
+            .unwrap_or(1);
+        let items = node.children().filter(|child| child.is(Name("li")));
 
-        if !self.options.use_bold_for_headers {
-            for _ in 1..=level {
-                self.add("#");
+        self.start_paragraph();
+        for (number, item) in (start..).zip(items) {
+            let marker = self.renderer.list_marker(ordered.then_some(number));
+            let content = self.render_children(&item);
+
+            // Keep items compact. Paragraphs in items would otherwise be
+            // separated by blank lines.
+            let content: Vec<&str> = content
+                .trim()
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .collect();
+
+            // Continuation lines (including nested lists) line up with the
+            // item's content.
+            let indent = " ".repeat(marker.chars().count());
+            self.add(marker);
+            self.add(prefix_lines(&content.join("\n"), &indent, false));
+            self.add("\n");
+        }
+    }
+
+    fn visit_table(&mut self, node: &Node) {
+        let is_cell = |node: &Node| node.is(Name("th")) || node.is(Name("td"));
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut alignments: Vec<Alignment> = Vec::new();
+        let mut has_header = false;
+
+        for row in node.find(Name("tr")) {
+            if rows.is_empty() {
+                has_header = row.children().any(|cell| cell.is(Name("th")));
+                alignments = row.children().filter(is_cell).map(get_alignment).collect();
             }
-            self.add(" ");
-        } else {
-            self.add("**");
+
+            // Cells have to fit on one line.
+            let cells = row
+                .children()
+                .filter(is_cell)
+                .map(|cell| {
+                    let content = if self.renderer.format_table_cells() {
+                        self.render_children(&cell)
+                    } else {
+                        self.render_plain_text(&cell)
+                    };
+                    content.split_whitespace().collect::<Vec<&str>>().join(" ")
+                })
+                .collect();
+            rows.push(cells);
         }
 
-        self.visit_children(node);
+        let column_count = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        if column_count == 0 {
+            return;
+        }
+        for row in rows.iter_mut() {
+            row.resize(column_count, String::new());
+        }
+        alignments.resize(column_count, Alignment::Left);
+
+        self.start_paragraph();
+        let table = self.renderer.table(&rows, &alignments, has_header);
+        self.add(table.trim_end_matches('\n'));
+        self.add("\n");
+    }
+
+    fn visit_details(&mut self, node: &Node) {
+        let summary = node
+            .children()
+            .find(|child| child.is(Name("summary")))
+            .map(|summary| self.render_children(&summary).trim().to_string())
+            .filter(|summary| !summary.is_empty())
+            .unwrap_or_else(|| DEFAULT_SUMMARY.to_string());
+
+        // Summaries are skipped by visit, so this is only the section's content.
+        let content = self.render_children(node);
+        let mut content = normalize_blank_lines(&content).trim().to_string();
+        if let Some(limit) = self.options.details_limit {
+            if content.chars().count() > limit {
+                content = self.shorten(&content, limit);
+            }
+        }
 
-        if self.options.use_bold_for_headers {
-            self.add("**");
+        let summary = self.renderer.strong(&summary);
+        self.start_paragraph();
+        self.add(summary);
+        self.add("\n");
+        if content.is_empty() {
+            return;
         }
+
+        let is_open = node.attr("open").is_some();
+        let spoiler = match self.options.details {
+            DetailsStyle::Spoiler if !is_open => self.renderer.spoiler(&content),
+            _ => None,
+        };
+        self.add(spoiler.unwrap_or(content));
         self.add("\n");
     }
 
+    /// Cut `content` short, to about `limit` characters, and link to the rest.
+    fn shorten(&self, content: &str, limit: usize) -> String {
+        // Ends on a paragraph or line boundary and closes any open code block.
+        let shortened = shorten_message(content, limit);
+        let read_more = match &self.options.read_more_url {
+            Some(url) => self.renderer.link("Read more on GitHub", url),
+            None => "…".to_string(),
+        };
+
+        format!("{}\n\n{}", shortened.trim_end(), read_more)
+    }
+
+    /// Make `url` absolute. Returns `None` for links within the page (e.g. `#heading`),
+    /// which can't be followed from elsewhere.
+    fn resolve(&self, url: &str) -> Option<String> {
+        if url.is_empty() || url.starts_with('#') {
+            return None;
+        }
+
+        let base = self.options.base_url.as_deref().unwrap_or(GITHUB_URL);
+        Some(resolve_url(url, base))
+    }
+
+    /// Output `image` as a link to `target` (or to the image, if `None`).
+    fn visit_image(&mut self, image: &Node, target: Option<&str>) {
+        // GitHub serves external images through a proxy, keeping the original
+        // URL in `data-canonical-src`. Links around them point to the proxy, too.
+        let proxied_src = image.attr("src");
+        let src = match image
+            .attr("data-canonical-src")
+            .or(proxied_src)
+            .and_then(|src| self.resolve(src))
+        {
+            Some(src) => src,
+            None => return,
+        };
+        let target = target.filter(|&target| Some(target) != proxied_src);
+        let alt = image
+            .attr("alt")
+            .map(|alt| alt.trim())
+            .filter(|alt| !alt.is_empty())
+            .unwrap_or("image");
+        let target = target
+            .and_then(|target| self.resolve(target))
+            .unwrap_or_else(|| src.clone());
+
+        let image = self.renderer.image(alt, &target);
+        self.add(image);
+        self.images.push(src);
+    }
+
+    /// Output a `g-emoji` element, or an `img` of one of GitHub's emoji. Their
+    /// fallback text or images are never output.
+    fn visit_emoji(&mut self, node: &Node) {
+        let alias = node
+            .attr("alias")
+            .or_else(|| node.attr("alt"))
+            .or_else(|| node.attr("title"))
+            .map(|alias| alias.trim().trim_matches(':'))
+            .filter(|alias| !alias.is_empty());
+        let unicode = get_emoji_unicode(node);
+
+        let emoji = match alias {
+            Some(alias) => self.renderer.emoji(alias, unicode.as_deref()),
+            None => unicode.unwrap_or_default(),
+        };
+        self.add(emoji);
+    }
+
+    fn visit_text(&mut self, node: &Node) {
+        let text = node.text();
+        if self.in_code_block {
+            self.add(text);
+            return;
+        }
+
+        // Outside of code blocks, runs of whitespace (including newlines)
+        // are shown as a single space.
+        let mut text = collapse_whitespace(&text);
+        let at_line_start = self.is_at_line_start();
+        let after_space = self.get_last_char() == Some(' ');
+        if at_line_start || after_space {
+            text = text.trim_start().to_string();
+        }
+
+        // Escape text (not code) so that it isn't mistaken for formatting.
+        if self.code_depth > 0 {
+            self.add(text);
+        } else {
+            let escaped = self.renderer.escape(&text, at_line_start);
+            self.add(escaped);
+        }
+    }
+
     /// Walk the DOM.
     fn visit(&mut self, node: &Node) {
         if node.is(Text) {
-            // We're a text node. Just output our text.
-            self.add(node.text());
+            self.visit_text(node);
         } else if node.is(Comment) {
             // Skip comments.
-        } else if node.is(Name("p")) || node.is(Name("div")) || node.is(Name("tr")) {
+        } else if node.is(Class("zeroclipboard-container")) {
+            // GitHub's "copy" button for code blocks.
+        } else if node.is(Name("summary")) {
+            // Output by visit_details.
+        } else if node.is(Name("p")) {
+            self.start_paragraph();
             self.visit_children(node);
 
             // Paragraphs have a trailing newline
             self.add("\n");
+        } else if node.is(Name("div")) || node.is(Name("tr")) {
+            self.start_line();
+            self.visit_children(node);
+            self.add("\n");
         } else if node.is(Name("br")) {
             // <br/>s don't have content.
             self.add("\n");
+        } else if node.is(Name("g-emoji")) || node.is(And(Name("img"), Class("emoji"))) {
+            self.visit_emoji(node);
+        } else if node.is(Name("img")) {
+            self.visit_image(node, None);
+        } else if node.is(Name("a")) && is_image_link(node) {
+            // GitHub links images to their full-size versions. Output just one link.
+            if let Some(image) = node.find(Name("img")).next() {
+                self.visit_image(&image, node.attr("href"));
+            }
         } else if node.is(Name("a")) {
-            // A link
-            let target = node.attr("href");
+            // A link. GitHub also links user mentions (`@user`) and issue
+            // references (`#123`), sometimes only with `data-url`.
+            let target = node
+                .attr("href")
+                .or_else(|| node.attr("data-url"))
+                .and_then(|target| self.resolve(target));
 
-            self.add("[");
-            self.visit_children(node);
-            self.add("]");
+            match target {
+                Some(target) => {
+                    let content = self.render_inline(node);
+                    let link = self.renderer.link(content.trim(), &target);
+                    self.add(link);
+                }
 
-            if let Some(target) = target {
-                self.add("(");
-                self.add(target);
-                self.add(") ");
+                // Not a link we can follow. Just output its content.
+                None => self.visit_children(node),
             }
-        } else if node.is(Name("i")) || node.is(Name("emph")) {
-            self.add("_");
-            self.visit_children(node);
-            self.add("_");
+        } else if node.is(Name("hr")) {
+            // A blank line before keeps the previous line from becoming a header.
+            self.start_paragraph();
+            let rule = self.renderer.horizontal_rule();
+            self.add(rule);
+            self.add("\n");
+        } else if node.is(Name("input")) && node.attr("type") == Some("checkbox") {
+            // A task list item
+            let checkbox = self.renderer.checkbox(node.attr("checked").is_some());
+            self.add(checkbox);
+
+            let followed_by_space = node
+                .next()
+                .is_some_and(|next| next.is(Text) && next.text().starts_with(char::is_whitespace));
+            if !followed_by_space {
+                self.add(" ");
+            }
+        } else if node.is(Name("del")) || node.is(Name("s")) || node.is(Name("strike")) {
+            self.visit_inline(node, |renderer, content| renderer.strikethrough(content));
+        } else if node.is(Name("i")) || node.is(Name("em")) || node.is(Name("emph")) {
+            self.visit_inline(node, |renderer, content| renderer.emphasis(content));
         } else if node.is(Name("b")) || node.is(Name("strong")) {
-            self.add("**");
+            self.visit_inline(node, |renderer, content| renderer.strong(content));
+        } else if node.is(Name("code")) && self.code_depth > 0 {
+            // Code in a code block (e.g. `<pre><code>`) is already formatted as code.
             self.visit_children(node);
-            self.add("**");
         } else if node.is(Name("code")) {
-            self.add("`");
-            self.visit_children(node);
-            self.add("`");
+            self.visit_inline_code(node);
         } else if node.is(Name("pre")) {
-            self.add("\n```\n");
-            self.visit_children(node);
-            self.add("\n```\n");
+            self.visit_code_block(node);
         } else if node.is(Name("h1")) {
             self.visit_header(node, 1);
         } else if node.is(Name("h2")) {
             self.visit_header(node, 2);
         } else if node.is(Name("h3")) {
             self.visit_header(node, 3);
-        } else if node.is(Name("quote")) {
-            self.add("\n> ");
-            self.visit_children(node);
+        } else if node.is(Name("h4")) {
+            self.visit_header(node, 4);
+        } else if node.is(Name("h5")) {
+            self.visit_header(node, 5);
+        } else if node.is(Name("h6")) {
+            self.visit_header(node, 6);
+        } else if node.is(Name("ul")) {
+            self.visit_list(node, false);
+        } else if node.is(Name("ol")) {
+            self.visit_list(node, true);
+        } else if node.is(Name("table")) {
+            self.visit_table(node);
+        } else if node.is(Name("details")) {
+            self.visit_details(node);
+        } else if node.is(Name("blockquote")) {
+            let content = self.render_children(node);
+
+            // Runs of blank lines would show up as runs of empty quoted lines.
+            let mut lines: Vec<&str> = Vec::new();
+            for line in content.trim().lines().map(|line| line.trim_end()) {
+                if !(line.is_empty() && lines.last().is_some_and(|last| last.is_empty())) {
+                    lines.push(line);
+                }
+            }
+
+            // Quote every line, so that the quote continues across paragraphs.
+            let quote = prefix_lines(&lines.join("\n"), self.renderer.quote_prefix(), true);
+            self.start_paragraph();
+            self.add(quote);
             self.add("\n");
         } else {
             self.visit_children(node);
         }
     }
 
-    /// Parse the given HTML and walk its tree.
-    pub fn start(&mut self, html: &str) {
-        let html = format!("<html>{}</html>", html);
-        let doc = Document::from(&html[..]);
-
-        if let Some(node) = doc.nth(0) {
+    /// Walk the tree of an already-parsed `document` (see [parse_html]).
+    pub fn walk(&mut self, document: &Document) {
+        if let Some(node) = document.nth(0) {
             self.visit(&node);
         }
     }
 
+    /// Parse the given HTML and walk its tree.
+    pub fn start(&mut self, html: &str) {
+        self.walk(&parse_html(html));
+    }
+
     /// Get the content this has accumulated.
     pub fn get_content(&self) -> String {
         let joined = self.buffer.join("");
 
-        joined.trim().to_string()
+        normalize_blank_lines(&joined).trim().to_string()
+    }
+
+    /// Get the URLs of the images this has visited.
+    pub fn get_images(&self) -> &[String] {
+        &self.images[..]
     }
 }
 
@@ -156,6 +579,191 @@ impl Default for MarkdownWalker {
     }
 }
 
+/// Add `prefix` to the start of every line in `text` but the first (unless
+/// `include_first`). Empty lines don't get trailing whitespace.
+fn prefix_lines(text: &str, prefix: &str, include_first: bool) -> String {
+    text.split('\n')
+        .enumerate()
+        .map(|(index, line)| {
+            if index == 0 && !include_first {
+                line.to_string()
+            } else if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Replace each run of whitespace in `text` with a single space.
+fn collapse_whitespace(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last_was_space = false;
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !last_was_space {
+                result.push(' ');
+            }
+            last_was_space = true;
+        } else {
+            result.push(c);
+            last_was_space = false;
+        }
+    }
+
+    result
+}
+
+/// Remove trailing whitespace and separate paragraphs by single blank lines.
+/// Code blocks (including quoted code blocks) are left as-is.
+fn normalize_blank_lines(text: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut fence: Option<String> = None;
+
+    for line in text.split('\n') {
+        let unquoted = line.trim_start_matches(['>', ' ']);
+        let is_fence = unquoted.starts_with("```");
+
+        match &fence {
+            Some(open_fence) => {
+                if is_fence && unquoted.trim_end() == open_fence {
+                    fence = None;
+                }
+                lines.push(line);
+            }
+            None => {
+                let line = line.trim_end();
+                if is_fence {
+                    fence = Some(unquoted.chars().take_while(|&c| c == '`').collect());
+                } else if line.is_empty() && lines.last().is_some_and(|last| last.is_empty()) {
+                    continue;
+                }
+                lines.push(line);
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// Get the language of the code in the `pre` element `node`, if known.
+/// GitHub marks highlighted code blocks with a `highlight-source-<language>`
+/// (or `highlight-text-<language>-...`) class on the containing `div`.
+fn get_code_language(node: &Node) -> Option<String> {
+    if let Some(language) = node.attr("lang") {
+        return Some(language.to_string());
+    }
+
+    let parent_classes = node.parent().and_then(|parent| parent.attr("class"));
+    let code_classes = node
+        .children()
+        .find(|child| child.is(Name("code")))
+        .and_then(|code| code.attr("class"));
+
+    parent_classes
+        .into_iter()
+        .chain(code_classes)
+        .flat_map(|classes| classes.split_whitespace())
+        .find_map(|class| {
+            if let Some(language) = class.strip_prefix("highlight-source-") {
+                Some(language.to_string())
+            } else if let Some(language) = class.strip_prefix("highlight-text-") {
+                language
+                    .split('-')
+                    .next()
+                    .map(|language| language.to_string())
+            } else {
+                class
+                    .strip_prefix("language-")
+                    .map(|language| language.to_string())
+            }
+        })
+}
+
+/// Get the GitHub emoji `node` (a `g-emoji` or an emoji `img`) as unicode, from
+/// its text or the name of its fallback image (e.g. `unicode/1f389.png`).
+/// Returns `None` for GitHub's custom emoji, which have no unicode equivalent.
+fn get_emoji_unicode(node: &Node) -> Option<String> {
+    let text = node.text();
+    let text = text.trim();
+    if !text.is_empty() && !text.is_ascii() {
+        return Some(text.to_string());
+    }
+
+    let src = node
+        .attr("fallback-src")
+        .or_else(|| node.attr("src"))
+        .or_else(|| {
+            node.find(Name("img"))
+                .next()
+                .and_then(|img| img.attr("src"))
+        })?;
+    let (path, file_name) = src.rsplit_once('/')?;
+    if !path.ends_with("/unicode") {
+        return None;
+    }
+
+    // Emoji made of several code points (e.g. flags) are named like `1f1fa-1f1f8.png`.
+    file_name
+        .split('.')
+        .next()?
+        .split('-')
+        .map(|hex| u32::from_str_radix(hex, 16).ok().and_then(char::from_u32))
+        .collect()
+}
+
+/// Returns true iff `link` contains only an image (and whitespace).
+fn is_image_link(link: &Node) -> bool {
+    let mut has_image = false;
+    for child in link.children() {
+        if child.is(Name("img")) {
+            has_image = true;
+        } else if !child.is(Text) || !child.text().trim().is_empty() {
+            return false;
+        }
+    }
+
+    has_image
+}
+
+/// Make `url` absolute, treating it as relative to the page at `base` (e.g.
+/// `https://github.com/org/repo/discussions/1`), as a browser would.
+fn resolve_url(url: &str, base: &str) -> String {
+    match Url::parse(base).and_then(|base| base.join(url)) {
+        Ok(url) => url.to_string(),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Get the alignment of a table `cell`, from its `align` attribute or style.
+fn get_alignment(cell: Node) -> Alignment {
+    let style = cell.attr("style").unwrap_or("").replace(' ', "");
+    let align = match cell.attr("align") {
+        Some(align) => align.to_string(),
+        None => style
+            .split(';')
+            .find_map(|rule| rule.strip_prefix("text-align:"))
+            .unwrap_or("")
+            .to_string(),
+    };
+
+    match align.to_lowercase().as_str() {
+        "center" => Alignment::Center,
+        "right" => Alignment::Right,
+        _ => Alignment::Left,
+    }
+}
+
+/// Parse an HTML fragment, so that it can be walked by one or more [MarkdownWalker]s.
+pub fn parse_html(html: &str) -> Document {
+    let html = format!("<html>{}</html>", html);
+
+    Document::from(&html[..])
+}
+
 /// Walks the given `html` using a [MarkdownWalker]
 /// and returns the collected content.
 pub fn html_to_md(html: &str) -> String {
@@ -168,18 +776,40 @@ pub fn html_to_md(html: &str) -> String {
 /// but works for more minimal markdown parsers. For example,
 /// headers are interpreted as bolded text, rather than full headers.
 pub fn html_to_md_minimal(html: &str) -> String {
-    let mut walker = MarkdownWalker::new();
+    html_to_md_minimal_with_images(html, GITHUB_URL).0
+}
+
+/// Like [html_to_md_minimal], but also returns the URLs of all images in `html`.
+/// Relative URLs are resolved against `base_url`.
+pub fn html_to_md_minimal_with_images(html: &str, base_url: &str) -> (String, Vec<String>) {
+    html_to_md_minimal_with_options(
+        html,
+        MarkdownOptions {
+            base_url: Some(base_url.to_string()),
+            ..Default::default()
+        },
+    )
+}
+
+/// Like [html_to_md_minimal_with_images], but configured by `options`.
+pub fn html_to_md_minimal_with_options(
+    html: &str,
+    options: MarkdownOptions,
+) -> (String, Vec<String>) {
+    let mut walker = MarkdownWalker::with_renderer(Box::new(DiscordRenderer));
 
-    walker.configure(MarkdownOptions {
-        use_bold_for_headers: true,
-    });
+    walker.configure(options);
     walker.start(html);
-    walker.get_content()
+
+    (walker.get_content(), walker.images)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::MarkdownWalker;
+    use super::{
+        html_to_md, html_to_md_minimal, html_to_md_minimal_with_images,
+        html_to_md_minimal_with_options, DetailsStyle, MarkdownOptions, MarkdownWalker,
+    };
 
     #[test]
     fn test_simple_html2md() {
@@ -193,8 +823,347 @@ mod tests {
 
 Of _a_ thing."#;
         let mut walker = MarkdownWalker::new();
-        walker.start(&html);
+        walker.start(html);
 
         assert_eq!(walker.get_content(), md);
     }
+
+    #[test]
+    fn test_formatting() {
+        let html = r#"
+<h4>Details</h4>
+<p><em>Remote</em>, <del>unpaid</del> <s>paid</s></p>
+<h6>Fine print</h6>
+<hr>
+<p>Apply by Friday.</p>
+        "#;
+        let md = r#"#### Details
+
+_Remote_, ~~unpaid~~ ~~paid~~
+
+###### Fine print
+
+---
+
+Apply by Friday."#;
+
+        assert_eq!(html_to_md(html), md);
+        assert!(html_to_md_minimal(html).starts_with("**Details**"));
+    }
+
+    #[test]
+    fn test_task_lists() {
+        let html = r#"
+<ul class="contains-task-list">
+<li class="task-list-item"><input type="checkbox" class="task-list-item-checkbox" checked disabled> Resume</li>
+<li class="task-list-item"><input type="checkbox" class="task-list-item-checkbox" disabled> Cover letter</li>
+</ul>
+        "#;
+
+        assert_eq!(html_to_md(html), "- [x] Resume\n- [ ] Cover letter");
+        assert_eq!(html_to_md_minimal(html), "- ☑ Resume\n- ☐ Cover letter");
+    }
+
+    #[test]
+    fn test_escaping() {
+        let html = r#"
+<p>Compute C_1 = 5*3 [approx.] ~2 | 3 with <code>a_b*c</code>.</p>
+<p># not a header</p>
+<p>2024. A good year. 2024 - 2025</p>
+        "#;
+        let md = r#"Compute C\_1 = 5\*3 \[approx.\] \~2 \| 3 with `a_b*c`.
+
+\# not a header
+
+2024\. A good year. 2024 - 2025"#;
+
+        assert_eq!(html_to_md(html), md);
+        assert_eq!(
+            html_to_md("<pre>x = y * z_1</pre>"),
+            "```\nx = y * z_1\n```"
+        );
+
+        // Bare URLs are linked as-is, so escaping them would break them.
+        assert_eq!(
+            html_to_md("<p>See https://example.com/foo_bar*baz~1 for C_1</p>"),
+            "See https://example.com/foo_bar*baz~1 for C\\_1"
+        );
+    }
+
+    #[test]
+    fn test_code() {
+        let html = r#"
+<p>Use <code>cargo run</code> or <code>``</code> and <code>a`b</code>.</p>
+<div class="highlight highlight-source-rust notranslate position-relative overflow-auto" dir="auto"><pre><span class="pl-k">fn</span> <span class="pl-en">main</span>() {
+    <span class="pl-en">println!</span>(<span class="pl-s">"```"</span>);
+}</pre><div class="zeroclipboard-container position-absolute right-0 top-0">
+    <clipboard-copy aria-label="Copy" class="ClipboardButton btn">Copy</clipboard-copy>
+  </div></div><pre><code>plain *code*
+</code></pre>
+        "#;
+        let md = r#"Use `cargo run` or ``` `` ``` and ``a`b``.
+
+````rust
+fn main() {
+    println!("```");
+}
+````
+
+```
+plain *code*
+```"#;
+
+        assert_eq!(html_to_md(html), md);
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let html = r#"
+<div class="markdown-body">
+    <p>
+        Lots   of
+        <b>indented</b>
+        text.
+    </p>
+
+
+    <div><p>Nested.</p></div>
+    <pre>
+  keep   this
+    indentation  </pre>
+    <p>Line<br>
+       break</p>
+</div>
+        "#;
+        let md = r#"Lots of **indented** text.
+
+Nested.
+
+```
+  keep   this
+    indentation  
+```
+
+Line
+break"#;
+
+        assert_eq!(html_to_md(html), md);
+    }
+
+    #[test]
+    fn test_lists() {
+        let html = r#"
+<p>Requirements:</p>
+<ul>
+<li>Rust</li>
+<li>Any of:
+<ol start="3">
+<li>SQL</li>
+<li><p>HTML</p><ul><li>and CSS</li></ul></li>
+</ol>
+</li>
+</ul>
+<p>Apply soon!</p>
+        "#;
+        let md = r#"Requirements:
+
+- Rust
+- Any of:
+  3. SQL
+  4. HTML
+     - and CSS
+
+Apply soon!"#;
+
+        assert_eq!(html_to_md(html), md);
+    }
+
+    #[test]
+    fn test_blockquotes() {
+        let html = r#"
+<blockquote>
+<p>First paragraph.</p>
+<p>Second paragraph:</p>
+<ul>
+<li>with a list</li>
+</ul>
+<blockquote>
+<p>A nested quote.</p>
+</blockquote>
+<pre>some code</pre>
+</blockquote>
+<p>After.</p>
+        "#;
+        let md = r#"> First paragraph.
+>
+> Second paragraph:
+>
+> - with a list
+>
+> > A nested quote.
+>
+> ```
+> some code
+> ```
+
+After."#;
+
+        assert_eq!(html_to_md(html), md);
+    }
+
+    #[test]
+    fn test_images() {
+        let html = r#"
+<p>Our office: <a target="_blank" href="/user-attachments/assets/1234"><img src="/user-attachments/assets/1234" alt="Office"></a></p>
+<p><img src="https://example.com/logo.png"></p>
+<p><a target="_blank" rel="noopener noreferrer nofollow" href="https://camo.githubusercontent.com/abc"><img src="https://camo.githubusercontent.com/abc" alt="Poster" data-canonical-src="https://example.com/poster.png"></a></p>
+        "#;
+        let (md, images) = html_to_md_minimal_with_images(html, "https://github.com");
+
+        assert_eq!(
+            md,
+            "Our office: [Office](https://github.com/user-attachments/assets/1234)\n\n\
+             [image](https://example.com/logo.png)\n\n\
+             [Poster](https://example.com/poster.png)"
+        );
+        assert_eq!(
+            images,
+            vec![
+                "https://github.com/user-attachments/assets/1234",
+                "https://example.com/logo.png",
+                "https://example.com/poster.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        let html = r##"
+<p>Thanks <a class="user-mention" href="/someone">@someone</a>,
+see <a class="issue-link js-issue-link" data-url="https://github.example.com/org/repo/issues/3">#3</a>,
+<a href="docs/apply.md">the docs</a>, <a href="../wiki">the wiki</a>, <a href="#how-to-apply">below</a>
+and <a href="mailto:jobs@example.com">email</a>.</p>
+        "##;
+        let md = "Thanks [@someone](https://github.example.com/someone), \
+                  see [#3](https://github.example.com/org/repo/issues/3), \
+                  [the docs](https://github.example.com/org/repo/discussions/docs/apply.md), \
+                  [the wiki](https://github.example.com/org/repo/wiki), below \
+                  and [email](mailto:jobs@example.com).";
+
+        let mut walker = MarkdownWalker::new();
+        walker.configure(MarkdownOptions {
+            base_url: Some("https://github.example.com/org/repo/discussions/5".to_string()),
+            ..Default::default()
+        });
+        walker.start(html);
+
+        assert_eq!(walker.get_content(), md);
+    }
+
+    const TABLE_HTML: &str = r#"
+<table>
+<thead>
+<tr><th>Role</th><th align="right">Stipend</th></tr>
+</thead>
+<tbody>
+<tr><td>Web <b>developer</b></td><td align="right">$20/hr</td></tr>
+<tr><td>Designer | UX</td><td align="right">$1000/month</td></tr>
+</tbody>
+</table>
+<p>Thanks!</p>
+    "#;
+
+    #[test]
+    fn test_pipe_tables() {
+        let md = r#"| Role | Stipend |
+| --- | ---: |
+| Web **developer** | $20/hr |
+| Designer \| UX | $1000/month |
+
+Thanks!"#;
+
+        assert_eq!(html_to_md(TABLE_HTML), md);
+    }
+
+    #[test]
+    fn test_monospace_tables() {
+        let md = r#"```
+Role          |     Stipend
+--------------+------------
+Web developer |      $20/hr
+Designer | UX | $1000/month
+```
+
+Thanks!"#;
+
+        assert_eq!(html_to_md_minimal(TABLE_HTML), md);
+    }
+
+    const DETAILS_HTML: &str = r#"
+<p>Paid internship.</p>
+<details>
+<summary>More <em>info</em></summary>
+<!-- Fill this in before posting! -->
+<p>Remote, 10 hours a week.</p>
+<p>Apply by Friday.</p>
+</details>
+<details open><p>Open by default.</p></details>
+    "#;
+
+    #[test]
+    fn test_details() {
+        let md = r#"Paid internship.
+
+**More _info_**
+Remote, 10 hours a week.
+
+Apply by Friday.
+
+**Details**
+Open by default."#;
+        assert_eq!(html_to_md(DETAILS_HTML), md);
+
+        let options = MarkdownOptions {
+            details: DetailsStyle::Spoiler,
+            ..Default::default()
+        };
+        let (md, _) = html_to_md_minimal_with_options(DETAILS_HTML, options);
+        assert_eq!(
+            md,
+            "Paid internship.\n\n\
+             **More _info_**\n||Remote, 10 hours a week.\n\nApply by Friday.||\n\n\
+             **Details**\nOpen by default."
+        );
+    }
+
+    #[test]
+    fn test_shortened_details() {
+        let options = MarkdownOptions {
+            details_limit: Some(40),
+            read_more_url: Some("https://github.com/org/repo/discussions/1".to_string()),
+            ..Default::default()
+        };
+        let (md, _) = html_to_md_minimal_with_options(DETAILS_HTML, options);
+        assert_eq!(
+            md,
+            "Paid internship.\n\n\
+             **More _info_**\nRemote, 10 hours a week.\n\n\
+             [Read more on GitHub](https://github.com/org/repo/discussions/1)\n\n\
+             **Details**\nOpen by default."
+        );
+    }
+
+    #[test]
+    fn test_emoji() {
+        let html = r#"
+<p>Congrats <g-emoji class="g-emoji" alias="tada" fallback-src="https://github.githubassets.com/images/icons/emoji/unicode/1f389.png">🎉</g-emoji>
+<g-emoji class="g-emoji" alias="us" fallback-src="https://github.githubassets.com/images/icons/emoji/unicode/1f1fa-1f1f8.png"><img class="emoji" alt="us" height="20" width="20" src="https://github.githubassets.com/images/icons/emoji/unicode/1f1fa-1f1f8.png"></g-emoji>
+<img class="emoji" title=":shipit:" alt=":shipit:" src="https://github.githubassets.com/images/icons/emoji/shipit.png" height="20" width="20" align="absmiddle"></p>
+<p><img class="emoji" title=":+1:" alt=":+1:" src="https://github.githubassets.com/images/icons/emoji/unicode/1f44d.png"></p>
+        "#;
+        let (md, images) = html_to_md_minimal_with_images(html, "https://github.com");
+
+        assert_eq!(md, "Congrats 🎉 🇺🇸 :shipit:\n\n👍");
+        assert!(images.is_empty());
+    }
 }
@@ -3,5 +3,12 @@
 //!
 
 pub mod bot;
+pub mod config;
+pub mod discord_markup;
+pub mod discussion_source;
+pub mod github_api;
 pub mod github_scraper;
 pub mod html_walker;
+pub mod message_split;
+pub mod renderer;
+pub mod state_store;
@@ -6,48 +6,98 @@
 /// to create and manage the bot.
 /// [This is a good tutorial on making a bot with Serenity](https://chilipepperhott.github.io/posts/intro-to-serenity/)
 use std::env;
-
-static ENV_VAR_TOKEN_NAME: &str = "DISCORD_BOT_TOKEN";
+use std::path::PathBuf;
 
 use forwarding_bot::bot;
+use forwarding_bot::config::{self, BotConfig};
 
-fn get_bot_token() -> Option<String> {
-    let args: Vec<String> = env::args().collect();
+/// What the user asked for on the command line.
+struct Arguments {
+    config_path: Option<PathBuf>,
+    token: Option<String>,
+}
 
-    if args.len() < 2 {
-        // We wern't given an argument.
-        // Check the environment.
-        return match env::var(ENV_VAR_TOKEN_NAME) {
-            Ok(s) => Some(s),
-            Err(_reason) => None,
-        };
-    }
+/// Parse the command line arguments. Returns `None` if the bot shouldn't start
+/// (e.g. `--help` was given or the arguments are invalid).
+fn parse_args() -> Option<Arguments> {
+    let args: Vec<String> = env::args().collect();
+    let mut result = Arguments {
+        config_path: None,
+        token: None,
+    };
 
-    if args[1] == "--help" {
-        print_usage(&args[0][..]);
-        return None;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        if arg == "--help" {
+            print_usage(&args[0][..]);
+            return None;
+        } else if arg == "--config" {
+            match rest.next() {
+                Some(path) => result.config_path = Some(PathBuf::from(path)),
+                None => {
+                    println!("Error: --config requires a path.");
+                    return None;
+                }
+            }
+        } else if result.token.is_none() {
+            result.token = Some(arg.clone());
+        } else {
+            println!("Error: Unexpected argument, {}.", arg);
+            print_usage(&args[0][..]);
+            return None;
+        }
     }
 
-    let token = args[1].clone();
-    Some(token)
+    Some(result)
 }
 
 #[tokio::main]
 async fn main() {
-    match get_bot_token() {
-        Some(token) => bot::start(token).await,
-        None => {
-            println!("Error: No API token provided.");
+    let args = match parse_args() {
+        Some(args) => args,
+        None => std::process::exit(1),
+    };
+
+    let config = match BotConfig::load(args.config_path.as_deref()) {
+        Ok(config) => config,
+        Err(why) => {
+            println!("Error: {}", why);
+            std::process::exit(1);
+        }
+    };
+
+    let token = match args.token {
+        Some(token) => Ok(token),
+        None => config.resolve_token(),
+    };
+
+    match token {
+        Ok(token) => bot::start(token, config).await,
+        Err(why) => {
+            println!("Error: {}", why);
             std::process::exit(1);
         }
     }
 }
 
 fn print_usage(app_name: &str) {
-    println!("Usage: {} <bot token>", app_name);
+    println!("Usage: {} [--config <bot.toml>] [<bot token>]", app_name);
     println!(
         " If <bot token> is not provided, the contents of
-the environment variable, {} are used.",
-        ENV_VAR_TOKEN_NAME
+the environment variable, {} are used, then the token configured in <bot.toml>.",
+        config::ENV_VAR_TOKEN_NAME
+    );
+    println!(
+        " These environment variables override values in <bot.toml>:
+  {}: the repository to fetch from (owner/repo)
+  {}: the discussion category to fetch from
+  {}: the GitHub host to fetch from
+  {}: comma-separated names of the channels to forward to
+  {}: seconds between checks for new opportunities",
+        config::ENV_VAR_REPOSITORY_NAME,
+        config::ENV_VAR_CATEGORY_NAME,
+        config::ENV_VAR_HOST_NAME,
+        config::ENV_VAR_CHANNELS_NAME,
+        config::ENV_VAR_POLL_INTERVAL_NAME
     );
 }
@@ -0,0 +1,592 @@
+//! Splits long messages into pieces that fit within Discord's length limits.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// The maximum length of a Discord message, in characters.
+pub const MESSAGE_LIMIT: usize = 2000;
+
+/// Characters reserved in each piece for numbering (e.g. `**(12/13)**\n`).
+const NUMBER_RESERVE: usize = 16;
+
+/// The shortest fence that opens or closes a code block.
+const MIN_FENCE: &str = "```";
+
+/// Split `text` into pieces of at most `limit` characters.
+///
+/// Pieces end on paragraph or line boundaries where possible, and never split a
+/// markdown link. Code blocks and spoilers split across pieces are closed at the
+/// end of one piece and re-opened (code blocks with the same language) at the
+/// start of the next.
+/// If `text` needs to be split, each piece after the first is numbered.
+///
+/// For example:
+/// ```
+/// # use forwarding_bot::message_split::split_message;
+/// let pieces = split_message("First paragraph.\n\nSecond paragraph.", 34);
+/// assert_eq!(pieces, vec!["First paragraph.", "**(2/2)**\nSecond paragraph."]);
+/// ```
+pub fn split_message(text: &str, limit: usize) -> Vec<String> {
+    if char_count(text) <= limit {
+        return vec![text.to_string()];
+    }
+
+    let pieces = Splitter::new(limit.saturating_sub(NUMBER_RESERVE).max(1)).split(text);
+    let count = pieces.len();
+
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(index, piece)| {
+            if index == 0 {
+                piece
+            } else {
+                format!("**({}/{})**\n{}", index + 1, count, piece)
+            }
+        })
+        .collect()
+}
+
+/// Get the start of `text`, cut short to at most `limit` characters. Like the
+/// first piece from [split_message], but without room reserved for numbering.
+pub fn shorten_message(text: &str, limit: usize) -> String {
+    Splitter::new(limit.max(1))
+        .split(text)
+        .into_iter()
+        .next()
+        .unwrap_or_default()
+}
+
+/// Returns true iff `text` is a piece, after the first, of a message split by [split_message].
+pub fn is_continuation(text: &str) -> bool {
+    lazy_static! {
+        static ref NUMBERING: Regex = Regex::new(r"^\*\*\(\d+/\d+\)\*\*\n").unwrap();
+    }
+
+    NUMBERING.is_match(text)
+}
+
+fn char_count(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Marks the start and end of a Discord spoiler.
+const SPOILER: &str = "||";
+
+/// Accumulates lines into pieces.
+struct Splitter {
+    limit: usize,
+    pieces: Vec<String>,
+    current: String,
+
+    /// The fence that opened the code block we're in, if any.
+    fence: Option<String>,
+
+    /// What precedes the code block's lines, if it's in a block quote or list item.
+    fence_prefix: String,
+
+    /// True iff we're in a spoiler.
+    in_spoiler: bool,
+
+    /// Byte offset in `current` of the most recent paragraph break outside of a code
+    /// block, and whether it was in a spoiler.
+    paragraph_break: Option<(usize, bool)>,
+}
+
+impl Splitter {
+    fn new(limit: usize) -> Self {
+        Splitter {
+            limit,
+            pieces: Vec::new(),
+            current: String::new(),
+            fence: None,
+            fence_prefix: String::new(),
+            in_spoiler: false,
+            paragraph_break: None,
+        }
+    }
+
+    fn split(mut self, text: &str) -> Vec<String> {
+        for line in text.split('\n') {
+            self.add_line(line);
+        }
+        self.flush();
+
+        self.pieces
+    }
+
+    /// Get the fence that closes the current code block, if in one. Code blocks
+    /// opened with more than three backticks must be closed with as many.
+    fn closing_fence(&self) -> Option<String> {
+        self.fence
+            .as_ref()
+            .map(|fence| fence.chars().take_while(|&c| c == '`').collect())
+    }
+
+    /// Get what ends a piece here, closing any code block or spoiler. Discord doesn't
+    /// hide code blocks in spoilers, so spoilers are left alone in code blocks.
+    fn closing(&self) -> String {
+        match self.closing_fence() {
+            Some(fence) => format!("\n{}{}", self.fence_prefix, fence),
+            None if self.in_spoiler => SPOILER.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Get what a piece continuing from here starts with, re-opening any code block
+    /// or spoiler.
+    fn reopening(&self) -> String {
+        match &self.fence {
+            Some(fence) => format!("{}{}\n", self.fence_prefix, fence),
+            None if self.in_spoiler => SPOILER.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Characters needed to close the current code block and spoiler, if in them.
+    fn closing_reserve(&self) -> usize {
+        char_count(&self.closing())
+    }
+
+    /// Returns true iff `line` can be added to the current piece.
+    fn fits(&self, line: &str) -> bool {
+        char_count(&self.current) + char_count(line) + 1 + self.closing_reserve() <= self.limit
+    }
+
+    fn add_line(&mut self, line: &str) {
+        if !self.fits(line) {
+            self.break_piece();
+        }
+
+        if !self.fits(line) {
+            // The line is too long to fit, even in an empty piece. Start one, so
+            // that the line isn't squeezed in after what's left of the last.
+            self.flush();
+
+            let used = char_count(&self.current) + 1 + self.closing_reserve();
+            let available = self.limit.saturating_sub(used).max(1);
+            let parts = if self.fence.is_some() {
+                // Whitespace is significant in code.
+                split_chars(line, available)
+            } else {
+                split_long_line(line, available)
+            };
+            let last = parts.len() - 1;
+
+            for (index, part) in parts.into_iter().enumerate() {
+                self.push_line(&part);
+                if index != last {
+                    self.flush();
+                }
+            }
+            return;
+        }
+
+        self.push_line(line);
+    }
+
+    /// Add `line` to the current piece, assuming it fits.
+    fn push_line(&mut self, line: &str) {
+        let (prefix, rest) = split_container(line);
+        let trimmed = rest.trim();
+        if self.current == self.reopening() {
+            if let Some(closing_fence) = self.closing_fence() {
+                if trimmed == closing_fence {
+                    // Don't re-open a code block just to close it.
+                    self.fence = None;
+                    self.current = self.reopening();
+                    return;
+                }
+            } else if line.trim().is_empty() {
+                // Pieces shouldn't start with blank lines.
+                return;
+            }
+        }
+
+        self.current.push_str(line);
+        self.current.push('\n');
+
+        if let Some(closing_fence) = self.closing_fence() {
+            if trimmed.starts_with(&closing_fence) && trimmed.trim_matches('`').is_empty() {
+                self.fence = None;
+            }
+        } else if trimmed.starts_with(MIN_FENCE) {
+            self.fence = Some(trimmed.to_string());
+            self.fence_prefix = continuation_prefix(prefix);
+        } else {
+            if count_spoiler_markers(line) % 2 == 1 {
+                self.in_spoiler = !self.in_spoiler;
+            }
+            if line.trim().is_empty() {
+                self.paragraph_break = Some((self.current.len(), self.in_spoiler));
+            }
+        }
+    }
+
+    /// End the current piece, preferably at the last paragraph break.
+    fn break_piece(&mut self) {
+        if let (Some((index, in_spoiler)), None) = (self.paragraph_break, &self.fence) {
+            if index < self.current.len() && !self.current[..index].trim().is_empty() {
+                // Move everything after the paragraph break to the next piece.
+                let mut rest = self.current.split_off(index);
+                if in_spoiler {
+                    self.current.truncate(self.current.trim_end().len());
+                    self.current.push_str(SPOILER);
+                    rest.insert_str(0, SPOILER);
+                }
+                self.paragraph_break = None;
+                self.push_piece();
+                self.current = rest;
+
+                return;
+            }
+        }
+
+        self.flush();
+    }
+
+    /// End the current piece here, closing and re-opening any code block and spoiler.
+    fn flush(&mut self) {
+        let reopening = self.reopening();
+
+        // Unless nothing has been added since the piece was re-opened.
+        if reopening.is_empty() || self.current.trim() != reopening.trim() {
+            // Keep any trailing whitespace, which may be part of code.
+            self.current
+                .truncate(self.current.trim_end_matches('\n').len());
+            self.current.push_str(&self.closing());
+            self.push_piece();
+            self.current = reopening;
+        }
+        self.paragraph_break = None;
+    }
+
+    fn push_piece(&mut self) {
+        let piece = self.current.trim_end();
+
+        if !piece.trim().is_empty() {
+            self.pieces.push(piece.to_string());
+        }
+        self.current.clear();
+    }
+}
+
+/// Split `line` into its indentation, block quote markers and list marker, and the rest.
+fn split_container(line: &str) -> (&str, &str) {
+    lazy_static! {
+        static ref CONTAINER: Regex =
+            Regex::new(r"^(?:[ \t]*>)*[ \t]*(?:(?:[-*+]|\d{1,9}[.)])[ \t]+)?").unwrap();
+    }
+
+    let end = CONTAINER.find(line).map_or(0, |container| container.end());
+    line.split_at(end)
+}
+
+/// Get what continues the block quote or list item that `prefix` (from
+/// [split_container]) starts, on a later line.
+fn continuation_prefix(prefix: &str) -> String {
+    prefix
+        .chars()
+        .map(|c| {
+            if c == '>' || c.is_whitespace() {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect()
+}
+
+/// Count the spoiler markers in `line`, ignoring any in inline code.
+fn count_spoiler_markers(line: &str) -> usize {
+    lazy_static! {
+        static ref INLINE_CODE: Regex = Regex::new(r"`+[^`]*`+").unwrap();
+    }
+
+    INLINE_CODE.replace_all(line, "").matches(SPOILER).count()
+}
+
+/// Split `line` into parts of at most `limit` characters, breaking at spaces
+/// and never inside a link (unless the link itself is too long).
+fn split_long_line(line: &str, limit: usize) -> Vec<String> {
+    lazy_static! {
+        static ref UNBREAKABLE: Regex =
+            Regex::new(r"\[[^\]]*\]\([^)]*\)|<https?://[^>]+>|\S+").unwrap();
+    }
+
+    let mut parts = Vec::new();
+    let mut current = String::new();
+
+    for word in UNBREAKABLE.find_iter(line).map(|m| m.as_str()) {
+        let separator = if current.is_empty() { 0 } else { 1 };
+        if char_count(&current) + separator + char_count(word) <= limit {
+            if separator > 0 {
+                current.push(' ');
+            }
+            current.push_str(word);
+            continue;
+        }
+
+        if !current.is_empty() {
+            parts.push(current);
+            current = String::new();
+        }
+
+        if char_count(word) <= limit {
+            current.push_str(word);
+        } else {
+            // No choice but to split the word.
+            let chars: Vec<char> = word.chars().collect();
+            for part in chars.chunks(limit) {
+                parts.push(part.iter().collect());
+            }
+            current = parts.pop().unwrap_or_default();
+        }
+    }
+
+    if !current.is_empty() || parts.is_empty() {
+        parts.push(current);
+    }
+
+    parts
+}
+
+/// Split `line` into parts of at most `limit` characters, keeping all of its whitespace.
+fn split_chars(line: &str, limit: usize) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+
+    chars
+        .chunks(limit)
+        .map(|part| part.iter().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{char_count, is_continuation, shorten_message, split_long_line, split_message};
+
+    fn assert_fits(pieces: &[String], limit: usize) {
+        for piece in pieces {
+            assert!(
+                char_count(piece) <= limit,
+                "{} characters (limit {}): {:?}",
+                char_count(piece),
+                limit,
+                piece
+            );
+        }
+    }
+
+    #[test]
+    fn test_short_messages_are_unchanged() {
+        assert_eq!(split_message("Hello!\n\n", 2000), vec!["Hello!\n\n"]);
+    }
+
+    #[test]
+    fn test_split_on_paragraphs() {
+        let paragraph = "word ".repeat(10).trim().to_string();
+        let text = vec![paragraph.clone(); 6].join("\n\n");
+        let pieces = split_message(&text, 140);
+
+        assert_fits(&pieces, 140);
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces[0], format!("{}\n\n{}", paragraph, paragraph));
+        assert_eq!(
+            pieces[1],
+            format!("**(2/3)**\n{}\n\n{}", paragraph, paragraph)
+        );
+    }
+
+    #[test]
+    fn test_code_blocks_are_reopened() {
+        let code: Vec<String> = (0..40).map(|i| format!("let x{} = {};", i, i)).collect();
+        let text = format!("Example:\n```rust\n{}\n```\nDone.", code.join("\n"));
+        let pieces = split_message(&text, 200);
+
+        assert_fits(&pieces, 200);
+        assert!(pieces.len() > 2);
+        for piece in pieces.iter() {
+            assert_eq!(
+                piece.matches("```").count() % 2,
+                0,
+                "Unbalanced fences in {:?}",
+                piece
+            );
+        }
+        assert!(pieces[1].contains("```rust\nlet x"), "{:?}", pieces[1]);
+        assert!(pieces.last().unwrap().ends_with("```\nDone."));
+
+        // Nothing should be lost.
+        let rejoined: String = pieces.join("\n");
+        for line in code {
+            assert!(rejoined.contains(&line));
+        }
+    }
+
+    #[test]
+    fn test_long_fences_are_closed() {
+        let code: Vec<String> = (0..40).map(|i| format!("print(\"```{}\")", i)).collect();
+        let text = format!("````python\n{}\n````", code.join("\n"));
+        let pieces = split_message(&text, 200);
+
+        assert_fits(&pieces, 200);
+        assert!(pieces.len() > 1);
+        assert!(pieces[0].ends_with("\n````"));
+        assert!(pieces[1].contains("````python\nprint"), "{:?}", pieces[1]);
+    }
+
+    #[test]
+    fn test_links_are_not_split() {
+        let link = "[a very important application form](https://example.com/apply)";
+        let text = format!("{} {} {}", "filler ".repeat(20), link, "filler ".repeat(20));
+        let pieces = split_message(&text, 120);
+
+        assert_fits(&pieces, 120);
+        assert!(pieces.iter().any(|piece| piece.contains(link)));
+    }
+
+    #[test]
+    fn test_long_line_after_long_paragraph() {
+        let text = format!("a\n\n{}\n{}", "b".repeat(80), "c ".repeat(60));
+        let pieces = split_message(&text, 100);
+
+        assert_fits(&pieces, 100);
+        assert_eq!(pieces.len(), 4, "{:?}", pieces);
+        assert!(pieces[3].ends_with(" c"), "{:?}", pieces[3]);
+    }
+
+    #[test]
+    fn test_long_code_lines_keep_whitespace() {
+        let line = format!("    let x = {};", "1  +  ".repeat(30));
+        let text = format!("```\n{}\n```", line);
+        let pieces = split_message(&text, 100);
+
+        assert_fits(&pieces, 100);
+        let code: String = pieces
+            .iter()
+            .map(|piece| {
+                let start = piece.find("```\n").unwrap() + 4;
+                piece[start..].trim_end_matches('`').trim_end_matches('\n')
+            })
+            .collect();
+        assert_eq!(code, line);
+    }
+
+    #[test]
+    fn test_spoilers_are_reopened() {
+        let paragraph = "A hidden detail about the opportunity.";
+        let code: Vec<String> = (0..10).map(|i| format!("step_{}();", i)).collect();
+        let text = format!(
+            "**More info**\n||{}\n\n{}||\n\n```\n{}\n```\n\nAfter `a || b`.",
+            [paragraph; 4].join("\n\n"),
+            [paragraph; 4].join("\n\n"),
+            code.join("\n")
+        );
+        let pieces = split_message(&text, 120);
+
+        assert_fits(&pieces, 120);
+        assert!(pieces.len() > 3);
+        for piece in pieces.iter() {
+            let body = piece.replace("`a || b`", "");
+            assert_eq!(
+                body.matches("||").count() % 2,
+                0,
+                "Unbalanced spoiler in {:?}",
+                piece
+            );
+            assert_eq!(
+                body.matches("```").count() % 2,
+                0,
+                "Unbalanced fences in {:?}",
+                piece
+            );
+        }
+        assert!(pieces[1].contains("\n||"), "{:?}", pieces[1]);
+        assert!(pieces.iter().all(|piece| !piece.contains("```\n```")));
+        assert!(pieces.last().unwrap().ends_with("\nAfter `a || b`."));
+    }
+
+    #[test]
+    fn test_code_in_spoilers_is_not_wrapped() {
+        let code: Vec<String> = (0..20).map(|i| format!("step_{}();", i)).collect();
+        let text = format!("||Secret:\n```\n{}\n```\nDone.||", code.join("\n"));
+        let pieces = split_message(&text, 80);
+
+        assert_fits(&pieces, 80);
+        assert!(pieces.len() > 2);
+        assert!(pieces[0].starts_with("||Secret:\n```\nstep_0();"));
+        assert!(pieces.last().unwrap().ends_with("```\nDone.||"));
+        for piece in pieces.iter() {
+            assert!(!piece.contains("||\n```"), "Wrapped fence in {:?}", piece);
+            assert!(!piece.contains("```\n||"), "Wrapped fence in {:?}", piece);
+            assert_eq!(piece.matches("```").count() % 2, 0, "{:?}", piece);
+        }
+    }
+
+    #[test]
+    fn test_code_in_containers_is_reopened() {
+        let code: Vec<String> = (0..20).map(|i| format!("> step_{}();", i)).collect();
+        let text = format!("> Quoted:\n> ```rust\n{}\n> ```", code.join("\n"));
+        let pieces = split_message(&text, 80);
+
+        assert_fits(&pieces, 80);
+        assert!(pieces.len() > 2);
+        assert!(pieces[0].ends_with("\n> ```"), "{:?}", pieces[0]);
+        for piece in pieces[1..].iter() {
+            let body = piece.split_once('\n').unwrap().1;
+            assert!(body.starts_with("> ```rust\n> step_"), "{:?}", piece);
+            assert!(body.ends_with("\n> ```"), "{:?}", piece);
+        }
+
+        let code: Vec<String> = (0..20).map(|i| format!("   step_{}();", i)).collect();
+        let text = format!("1. Build it:\n   ```\n{}\n   ```", code.join("\n"));
+        let pieces = split_message(&text, 80);
+
+        assert_fits(&pieces, 80);
+        assert!(pieces[0].starts_with("1. Build it:\n   ```\n"));
+        assert!(pieces[0].ends_with("\n   ```"), "{:?}", pieces[0]);
+        for piece in pieces[1..].iter() {
+            let body = piece.split_once('\n').unwrap().1;
+            assert!(body.starts_with("   ```\n   step_"), "{:?}", piece);
+            assert!(body.ends_with("\n   ```"), "{:?}", piece);
+        }
+
+        // A fence can also start a list item.
+        let code: Vec<String> = (0..20).map(|i| format!("  step_{}();", i)).collect();
+        let text = format!("- ```\n{}\n  ```", code.join("\n"));
+        let pieces = split_message(&text, 80);
+
+        assert!(pieces[0].ends_with("\n  ```"), "{:?}", pieces[0]);
+        let body = pieces[1].split_once('\n').unwrap().1;
+        assert!(body.starts_with("  ```\n  step_"), "{:?}", pieces[1]);
+    }
+
+    #[test]
+    fn test_shorten_message() {
+        let text = "Intro.\n\n```\nline 1\nline 2\nline 3\n```";
+        assert_eq!(
+            shorten_message(text, 30),
+            "Intro.\n\n```\nline 1\nline 2\n```"
+        );
+        assert_eq!(shorten_message(text, 100), text);
+    }
+
+    #[test]
+    fn test_is_continuation() {
+        let pieces = split_message(&"Paragraph.\n\n".repeat(10), 40);
+        assert!(!is_continuation(&pieces[0]));
+        assert!(pieces[1..].iter().all(|piece| is_continuation(piece)));
+        assert!(!is_continuation(
+            "**(2/3)** isn't numbering without a newline"
+        ));
+    }
+
+    #[test]
+    fn test_split_long_words() {
+        assert_eq!(
+            split_long_line("abcdefgh ij", 3),
+            vec!["abc", "def", "gh", "ij"]
+        );
+        assert_eq!(split_long_line("", 3), vec![""]);
+    }
+}
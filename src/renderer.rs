@@ -0,0 +1,535 @@
+//! Output formats for [MarkdownWalker](crate::html_walker::MarkdownWalker).
+
+use std::cmp::max;
+
+use crate::discord_markup::find_urls;
+
+/// How a table column's content is aligned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// Formats the content a [MarkdownWalker](crate::html_walker::MarkdownWalker) visits.
+///
+/// Content passed to these methods has already been rendered (and escaped) by this
+/// renderer, except for code, which is passed as-is.
+/// The default implementations output GitHub-flavored markdown.
+pub trait Renderer {
+    /// Escape the characters in `text` that would otherwise be treated as formatting.
+    /// If `at_line_start`, `text` starts a line (so, for example, a leading `#`
+    /// would make a header).
+    fn escape(&self, text: &str, at_line_start: bool) -> String {
+        escape_markdown(text, at_line_start)
+    }
+
+    /// Format a header. `level` is between 1 and 6 (for `h1` through `h6`).
+    fn header(&self, level: u8, content: &str) -> String {
+        format!("{} {}", "#".repeat(level as usize), content)
+    }
+
+    fn emphasis(&self, content: &str) -> String {
+        format!("_{}_", content)
+    }
+
+    fn strong(&self, content: &str) -> String {
+        format!("**{}**", content)
+    }
+
+    fn strikethrough(&self, content: &str) -> String {
+        format!("~~{}~~", content)
+    }
+
+    fn inline_code(&self, code: &str) -> String {
+        // The fence has to be longer than any run of backticks in the code.
+        let fence = "`".repeat(longest_backtick_run(code) + 1);
+        let padding = if code.starts_with('`') || code.ends_with('`') {
+            " "
+        } else {
+            ""
+        };
+
+        format!("{}{}{}{}{}", fence, padding, code, padding, fence)
+    }
+
+    /// Format a block of `code`, written in `language` (empty if unknown).
+    fn code_block(&self, language: &str, code: &str) -> String {
+        let fence = "`".repeat(max(3, longest_backtick_run(code) + 1));
+
+        format!("{}{}\n{}\n{}", fence, language, code, fence)
+    }
+
+    /// Format a link to the absolute URL `target`.
+    fn link(&self, content: &str, target: &str) -> String {
+        format!("[{}]({})", content, target)
+    }
+
+    /// Format an image, described by `alt` (not yet escaped), linking to `target`.
+    fn image(&self, alt: &str, target: &str) -> String {
+        self.link(&self.escape(alt, false), target)
+    }
+
+    /// Format the emoji GitHub calls `alias` (e.g. `tada`). `unicode` is the emoji
+    /// itself (e.g. 🎉), or `None` for GitHub's custom emoji (e.g. `octocat`).
+    fn emoji(&self, alias: &str, unicode: Option<&str>) -> String {
+        match unicode {
+            Some(unicode) => unicode.to_string(),
+            None => self.escape(&format!(":{}:", alias), false),
+        }
+    }
+
+    /// Get the marker for a list item: numbered `number` in an ordered
+    /// list, or `None` in an unordered list.
+    fn list_marker(&self, number: Option<i64>) -> String {
+        match number {
+            Some(number) => format!("{}. ", number),
+            None => "- ".to_string(),
+        }
+    }
+
+    /// Format a task list checkbox.
+    fn checkbox(&self, checked: bool) -> String {
+        if checked { "[x]" } else { "[ ]" }.to_string()
+    }
+
+    /// Get what each line of a quote starts with.
+    fn quote_prefix(&self) -> &str {
+        "> "
+    }
+
+    fn horizontal_rule(&self) -> String {
+        "---".to_string()
+    }
+
+    /// Format `content` (one or more paragraphs) as a spoiler, hidden until clicked.
+    /// Returns `None` if spoilers aren't supported.
+    fn spoiler(&self, _content: &str) -> Option<String> {
+        None
+    }
+
+    /// Returns true iff table cells should be formatted (and escaped) by this renderer.
+    /// Tables output as code shouldn't be, since the formatting would show. Their
+    /// cells are plain text.
+    fn format_table_cells(&self) -> bool {
+        true
+    }
+
+    /// Format a table. Each of the `rows` has one cell per column, and each
+    /// column is aligned as in `alignments`. If `has_header`, the first row is
+    /// the table's header.
+    fn table(&self, rows: &[Vec<String>], alignments: &[Alignment], _has_header: bool) -> String {
+        pipe_table(rows, alignments)
+    }
+}
+
+/// Outputs GitHub-flavored markdown.
+pub struct MarkdownRenderer;
+
+impl Renderer for MarkdownRenderer {}
+
+/// Outputs the subset of markdown Discord understands. For example, headers are
+/// bold text and tables are aligned with spaces in a code block.
+pub struct DiscordRenderer;
+
+impl Renderer for DiscordRenderer {
+    fn header(&self, _level: u8, content: &str) -> String {
+        self.strong(content)
+    }
+
+    fn checkbox(&self, checked: bool) -> String {
+        unicode_checkbox(checked)
+    }
+
+    fn spoiler(&self, content: &str) -> Option<String> {
+        // Code block fences have to be on lines of their own.
+        let start = if content.starts_with("```") { "\n" } else { "" };
+        let end = if content.ends_with("```") { "\n" } else { "" };
+
+        Some(format!("||{}{}{}||", start, content, end))
+    }
+
+    fn format_table_cells(&self) -> bool {
+        false
+    }
+
+    fn table(&self, rows: &[Vec<String>], alignments: &[Alignment], has_header: bool) -> String {
+        format!("```\n{}```", monospace_table(rows, alignments, has_header))
+    }
+}
+
+/// Outputs plain text, without any formatting.
+pub struct PlainTextRenderer;
+
+impl Renderer for PlainTextRenderer {
+    fn escape(&self, text: &str, _at_line_start: bool) -> String {
+        text.to_string()
+    }
+
+    fn header(&self, _level: u8, content: &str) -> String {
+        content.to_string()
+    }
+
+    fn emphasis(&self, content: &str) -> String {
+        content.to_string()
+    }
+
+    fn strong(&self, content: &str) -> String {
+        content.to_string()
+    }
+
+    fn strikethrough(&self, content: &str) -> String {
+        content.to_string()
+    }
+
+    fn inline_code(&self, code: &str) -> String {
+        code.to_string()
+    }
+
+    fn code_block(&self, _language: &str, code: &str) -> String {
+        indent(code, "    ")
+    }
+
+    fn link(&self, content: &str, target: &str) -> String {
+        if content.is_empty() || content == target {
+            target.to_string()
+        } else {
+            format!("{} ({})", content, target)
+        }
+    }
+
+    fn list_marker(&self, number: Option<i64>) -> String {
+        match number {
+            Some(number) => format!("{}. ", number),
+            None => "• ".to_string(),
+        }
+    }
+
+    fn checkbox(&self, checked: bool) -> String {
+        unicode_checkbox(checked)
+    }
+
+    fn format_table_cells(&self) -> bool {
+        false
+    }
+
+    fn table(&self, rows: &[Vec<String>], alignments: &[Alignment], has_header: bool) -> String {
+        monospace_table(rows, alignments, has_header)
+    }
+}
+
+/// Outputs Slack's markdown variant, mrkdwn.
+pub struct SlackRenderer;
+
+impl Renderer for SlackRenderer {
+    /// Slack has no way to escape formatting characters, but `&`, `<` and `>`
+    /// must be written as HTML entities.
+    fn escape(&self, text: &str, _at_line_start: bool) -> String {
+        escape_slack(text)
+    }
+
+    fn header(&self, _level: u8, content: &str) -> String {
+        self.strong(content)
+    }
+
+    fn strong(&self, content: &str) -> String {
+        format!("*{}*", content)
+    }
+
+    fn strikethrough(&self, content: &str) -> String {
+        format!("~{}~", content)
+    }
+
+    fn inline_code(&self, code: &str) -> String {
+        format!("`{}`", escape_slack(code))
+    }
+
+    fn code_block(&self, _language: &str, code: &str) -> String {
+        format!("```\n{}\n```", escape_slack(code))
+    }
+
+    fn link(&self, content: &str, target: &str) -> String {
+        if content.is_empty() {
+            format!("<{}>", target)
+        } else {
+            format!("<{}|{}>", target, content.replace('|', "¦"))
+        }
+    }
+
+    /// Slack understands the same shortcodes as GitHub.
+    fn emoji(&self, alias: &str, _unicode: Option<&str>) -> String {
+        format!(":{}:", alias)
+    }
+
+    fn list_marker(&self, number: Option<i64>) -> String {
+        match number {
+            Some(number) => format!("{}. ", number),
+            None => "• ".to_string(),
+        }
+    }
+
+    fn checkbox(&self, checked: bool) -> String {
+        unicode_checkbox(checked)
+    }
+
+    fn format_table_cells(&self) -> bool {
+        false
+    }
+
+    fn table(&self, rows: &[Vec<String>], alignments: &[Alignment], has_header: bool) -> String {
+        format!(
+            "```\n{}```",
+            escape_slack(&monospace_table(rows, alignments, has_header))
+        )
+    }
+}
+
+fn unicode_checkbox(checked: bool) -> String {
+    if checked { "☑" } else { "☐" }.to_string()
+}
+
+/// Escape the characters Slack treats specially.
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Add `prefix` to the start of each non-empty line in `text`.
+fn indent(text: &str, prefix: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            if line.is_empty() {
+                line.to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Escape the characters in `text` that markdown would treat as formatting.
+/// If `at_line_start`, `text` starts a line (so, for example, a leading `#`
+/// would make a header). Bare URLs are left as-is, since they're linked
+/// automatically, and escaping them would change their targets.
+fn escape_markdown(text: &str, at_line_start: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last_end = 0;
+    for url in find_urls(text) {
+        let prose = &text[last_end..url.start];
+        result.push_str(&escape_prose(prose, at_line_start && last_end == 0));
+        result.push_str(&text[url.clone()]);
+        last_end = url.end;
+    }
+    result.push_str(&escape_prose(
+        &text[last_end..],
+        at_line_start && last_end == 0,
+    ));
+
+    result
+}
+
+/// Like [escape_markdown], but escapes URLs, too.
+fn escape_prose(text: &str, at_line_start: bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut line_start = at_line_start;
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let next = chars.get(index + 1).copied();
+
+        if line_start && !c.is_whitespace() {
+            line_start = false;
+
+            // Headers, quotes and list items are only formatting at the start of a line.
+            let is_bullet = (c == '-' || c == '+') && next.is_none_or(char::is_whitespace);
+            if c == '#' || c == '>' || is_bullet {
+                result.push('\\');
+            } else if c.is_ascii_digit() {
+                // An ordered list item (e.g. `1.`)?
+                let digits = chars[index..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                let after = chars.get(index + digits).copied();
+                if after == Some('.') || after == Some(')') {
+                    result.extend(&chars[index..index + digits]);
+                    result.push('\\');
+                    index += digits;
+                    continue;
+                }
+            }
+        }
+
+        if c == '\n' {
+            line_start = true;
+        }
+
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '[' | ']') {
+            result.push('\\');
+        }
+        result.push(c);
+        index += 1;
+    }
+
+    result
+}
+
+/// Get the length of the longest run of backticks in `text`.
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`')
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Escape the `|`s in `text` that aren't already escaped.
+fn escape_pipes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if c == '|' && !escaped {
+            result.push('\\');
+        }
+        escaped = c == '\\' && !escaped;
+        result.push(c);
+    }
+
+    result
+}
+
+/// Format the given `rows` as a GitHub-flavored markdown table.
+/// The first row is always the header.
+fn pipe_table(rows: &[Vec<String>], alignments: &[Alignment]) -> String {
+    let format_row = |row: &Vec<String>| {
+        let cells: Vec<String> = row.iter().map(|cell| escape_pipes(cell)).collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+    let delimiters: Vec<&str> = alignments
+        .iter()
+        .map(|alignment| match alignment {
+            Alignment::Left => "---",
+            Alignment::Center => ":---:",
+            Alignment::Right => "---:",
+        })
+        .collect();
+
+    let mut result = format_row(&rows[0]);
+    result.push_str(&format!("| {} |\n", delimiters.join(" | ")));
+    for row in rows[1..].iter() {
+        result.push_str(&format_row(row));
+    }
+
+    result
+}
+
+/// Format the given `rows` as a table with space-aligned columns.
+fn monospace_table(rows: &[Vec<String>], alignments: &[Alignment], has_header: bool) -> String {
+    let widths: Vec<usize> = (0..alignments.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let format_row = |row: &Vec<String>| {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths.iter().zip(alignments.iter()))
+            .map(|(cell, (&width, alignment))| match alignment {
+                Alignment::Left => format!("{:<width$}", cell, width = width),
+                Alignment::Center => format!("{:^width$}", cell, width = width),
+                Alignment::Right => format!("{:>width$}", cell, width = width),
+            })
+            .collect();
+
+        format!("{}\n", cells.join(" | ").trim_end())
+    };
+
+    let mut result = String::new();
+    for (index, row) in rows.iter().enumerate() {
+        result.push_str(&format_row(row));
+
+        if index == 0 && has_header {
+            let separators: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+            result.push_str(&format!("{}\n", separators.join("-+-")));
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_markdown, PlainTextRenderer, SlackRenderer};
+    use crate::html_walker::{parse_html, MarkdownWalker};
+
+    const HTML: &str = r#"
+<h2>Web developer <g-emoji class="g-emoji" alias="tada" fallback-src="https://github.githubassets.com/images/icons/emoji/unicode/1f389.png">🎉</g-emoji></h2>
+<p>Paid, <em>remote</em> &amp; <b>part-time</b>. Uses <code>a &lt; b</code>.</p>
+<ul>
+<li><a href="/apply">Apply here</a></li>
+<li><input type="checkbox" disabled checked> Resume</li>
+</ul>
+<pre lang="rust">fn main() {}</pre>
+    "#;
+
+    #[test]
+    fn test_escape_markdown_skips_urls() {
+        assert_eq!(
+            escape_markdown("# See https://example.com/a_b*c~d_ and a_b", true),
+            "\\# See https://example.com/a_b*c~d_ and a\\_b"
+        );
+        assert_eq!(
+            escape_markdown("https://example.com/a_b\n# Header", true),
+            "https://example.com/a_b\n\\# Header"
+        );
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let document = parse_html(HTML);
+        let mut walker = MarkdownWalker::with_renderer(Box::new(PlainTextRenderer));
+        walker.walk(&document);
+
+        assert_eq!(
+            walker.get_content(),
+            "Web developer 🎉
+
+Paid, remote & part-time. Uses a < b.
+
+• Apply here (https://github.com/apply)
+• ☑ Resume
+
+    fn main() {}"
+        );
+    }
+
+    #[test]
+    fn test_slack_mrkdwn() {
+        let document = parse_html(HTML);
+        let mut walker = MarkdownWalker::with_renderer(Box::new(SlackRenderer));
+        walker.walk(&document);
+
+        assert_eq!(
+            walker.get_content(),
+            "*Web developer :tada:*
+
+Paid, _remote_ &amp; *part-time*. Uses `a &lt; b`.
+
+• <https://github.com/apply|Apply here>
+• ☑ Resume
+
+```
+fn main() {}
+```"
+        );
+    }
+}
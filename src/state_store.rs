@@ -0,0 +1,662 @@
+//! Remembers which discussions have been forwarded to which channels.
+//!
+//! Forwarding consults a [StateStore] before posting and records each post
+//! after it is sent, so that restarting the bot (or deleting one of its messages)
+//! never causes an opportunity to be forwarded twice.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::config::{StateBackend, StateConfig};
+
+/// A discussion that has been forwarded to a channel.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardedRecord {
+    /// The Discord channel the discussion was forwarded to.
+    pub channel_id: u64,
+
+    /// Identifies where the discussion came from. See
+    /// [crate::github_scraper::ScraperConfig::get_source_key].
+    pub source: String,
+
+    /// The discussion's number within its repository.
+    pub discussion_id: u64,
+
+    /// The Discord messages the discussion was forwarded as.
+    /// Empty if the messages are unknown (e.g. the record was recovered from channel history).
+    pub message_ids: Vec<u64>,
+
+    /// When the discussion had last been edited when its messages were sent (or
+    /// last updated), or `None` if it hadn't been.
+    #[serde(default)]
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+/// Errors encountered while reading or writing forwarding state.
+#[derive(Debug)]
+pub enum StateError {
+    Io(PathBuf, std::io::Error),
+    Json(PathBuf, serde_json::Error),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+}
+
+/// Storage for [ForwardedRecord]s.
+pub trait StateStore: Send + Sync {
+    /// Get the record of discussion `discussion_id` from `source` being forwarded to
+    /// `channel_id`, if it has been.
+    fn get_record(
+        &self,
+        channel_id: u64,
+        source: &str,
+        discussion_id: u64,
+    ) -> Result<Option<ForwardedRecord>, StateError>;
+
+    /// Get the greatest id of the discussions from `source` that were forwarded
+    /// to `channel_id`, or `None` if nothing from `source` has been forwarded there.
+    fn get_last_forwarded_id(
+        &self,
+        channel_id: u64,
+        source: &str,
+    ) -> Result<Option<u64>, StateError>;
+
+    /// Get the records of (up to) the `count` discussions from `source` with the greatest
+    /// ids that were forwarded to `channel_id`, greatest id first.
+    fn get_recent_records(
+        &self,
+        channel_id: u64,
+        source: &str,
+        count: usize,
+    ) -> Result<Vec<ForwardedRecord>, StateError>;
+
+    /// Remember that a discussion was forwarded. Replaces any existing record for the
+    /// same channel, source and discussion.
+    fn record_forwarded(&self, record: ForwardedRecord) -> Result<(), StateError>;
+
+    /// Returns true iff discussion `discussion_id` from `source` has been forwarded
+    /// to `channel_id`.
+    fn is_forwarded(
+        &self,
+        channel_id: u64,
+        source: &str,
+        discussion_id: u64,
+    ) -> Result<bool, StateError> {
+        Ok(self
+            .get_record(channel_id, source, discussion_id)?
+            .is_some())
+    }
+}
+
+/// A [StateStore] that forgets everything when the bot stops.
+#[derive(Default)]
+pub struct MemoryStore {
+    records: Mutex<Vec<ForwardedRecord>>,
+}
+
+/// A [StateStore] backed by a JSON file. The entire file is rewritten on each change.
+pub struct JsonFileStore {
+    path: PathBuf,
+    records: Mutex<Vec<ForwardedRecord>>,
+}
+
+/// A [StateStore] backed by a SQLite database.
+#[cfg(feature = "sqlite")]
+pub struct SqliteStore {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+/// Open the store described by `config`.
+pub fn open(config: &StateConfig) -> Result<Arc<dyn StateStore>, StateError> {
+    let path = config.get_path();
+
+    Ok(match config.backend {
+        StateBackend::Memory => Arc::new(MemoryStore::new()),
+        StateBackend::Json => Arc::new(JsonFileStore::open(&path)?),
+        #[cfg(feature = "sqlite")]
+        StateBackend::Sqlite => Arc::new(SqliteStore::open(&path)?),
+    })
+}
+
+impl std::fmt::Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateError::Io(path, why) => {
+                write!(f, "Unable to access state file {}: {}", path.display(), why)
+            }
+            StateError::Json(path, why) => {
+                write!(f, "Invalid state file {}: {}", path.display(), why)
+            }
+            #[cfg(feature = "sqlite")]
+            StateError::Sqlite(why) => write!(f, "State database error: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for StateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StateError::Io(_, why) => Some(why),
+            StateError::Json(_, why) => Some(why),
+            #[cfg(feature = "sqlite")]
+            StateError::Sqlite(why) => Some(why),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for StateError {
+    fn from(why: rusqlite::Error) -> Self {
+        StateError::Sqlite(why)
+    }
+}
+
+/// Returns true iff `record` describes the given discussion in the given channel.
+fn record_matches(
+    record: &ForwardedRecord,
+    channel_id: u64,
+    source: &str,
+    discussion_id: u64,
+) -> bool {
+    record.channel_id == channel_id
+        && record.source == source
+        && record.discussion_id == discussion_id
+}
+
+/// Implements [StateStore::get_record] for a list of records.
+fn find_record(
+    records: &[ForwardedRecord],
+    channel_id: u64,
+    source: &str,
+    discussion_id: u64,
+) -> Option<ForwardedRecord> {
+    records
+        .iter()
+        .find(|record| record_matches(record, channel_id, source, discussion_id))
+        .cloned()
+}
+
+/// Implements [StateStore::get_last_forwarded_id] for a list of records.
+fn find_last_id(records: &[ForwardedRecord], channel_id: u64, source: &str) -> Option<u64> {
+    records
+        .iter()
+        .filter(|record| record.channel_id == channel_id && record.source == source)
+        .map(|record| record.discussion_id)
+        .max()
+}
+
+/// Implements [StateStore::get_recent_records] for a list of records.
+fn find_recent(
+    records: &[ForwardedRecord],
+    channel_id: u64,
+    source: &str,
+    count: usize,
+) -> Vec<ForwardedRecord> {
+    let mut recent: Vec<ForwardedRecord> = records
+        .iter()
+        .filter(|record| record.channel_id == channel_id && record.source == source)
+        .cloned()
+        .collect();
+    recent.sort_by_key(|record| std::cmp::Reverse(record.discussion_id));
+    recent.truncate(count);
+
+    recent
+}
+
+/// Adds `record` to `records`, replacing any record for the same discussion and channel.
+fn insert_record(records: &mut Vec<ForwardedRecord>, record: ForwardedRecord) {
+    records.retain(|other| {
+        !record_matches(
+            other,
+            record.channel_id,
+            &record.source,
+            record.discussion_id,
+        )
+    });
+    records.push(record);
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl StateStore for MemoryStore {
+    fn get_record(
+        &self,
+        channel_id: u64,
+        source: &str,
+        discussion_id: u64,
+    ) -> Result<Option<ForwardedRecord>, StateError> {
+        let records = self.records.lock().unwrap();
+        Ok(find_record(&records, channel_id, source, discussion_id))
+    }
+
+    fn get_last_forwarded_id(
+        &self,
+        channel_id: u64,
+        source: &str,
+    ) -> Result<Option<u64>, StateError> {
+        let records = self.records.lock().unwrap();
+        Ok(find_last_id(&records, channel_id, source))
+    }
+
+    fn get_recent_records(
+        &self,
+        channel_id: u64,
+        source: &str,
+        count: usize,
+    ) -> Result<Vec<ForwardedRecord>, StateError> {
+        let records = self.records.lock().unwrap();
+        Ok(find_recent(&records, channel_id, source, count))
+    }
+
+    fn record_forwarded(&self, record: ForwardedRecord) -> Result<(), StateError> {
+        insert_record(&mut self.records.lock().unwrap(), record);
+        Ok(())
+    }
+}
+
+impl JsonFileStore {
+    /// Open the store at `path`. The file is created when the first record is added.
+    pub fn open(path: &Path) -> Result<Self, StateError> {
+        let records = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|why| StateError::Json(path.to_path_buf(), why))?,
+            Err(why) if why.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(why) => return Err(StateError::Io(path.to_path_buf(), why)),
+        };
+
+        Ok(JsonFileStore {
+            path: path.to_path_buf(),
+            records: Mutex::new(records),
+        })
+    }
+
+    /// Write `records` to this' file. A temporary file is written, then
+    /// renamed, so a crash while saving can't leave a truncated file.
+    fn save(&self, records: &[ForwardedRecord]) -> Result<(), StateError> {
+        let text = serde_json::to_string_pretty(records)
+            .map_err(|why| StateError::Json(self.path.clone(), why))?;
+
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+
+        std::fs::write(&temp_path, text).map_err(|why| StateError::Io(temp_path.clone(), why))?;
+        std::fs::rename(&temp_path, &self.path)
+            .map_err(|why| StateError::Io(self.path.clone(), why))?;
+
+        Ok(())
+    }
+}
+
+impl StateStore for JsonFileStore {
+    fn get_record(
+        &self,
+        channel_id: u64,
+        source: &str,
+        discussion_id: u64,
+    ) -> Result<Option<ForwardedRecord>, StateError> {
+        let records = self.records.lock().unwrap();
+        Ok(find_record(&records, channel_id, source, discussion_id))
+    }
+
+    fn get_last_forwarded_id(
+        &self,
+        channel_id: u64,
+        source: &str,
+    ) -> Result<Option<u64>, StateError> {
+        let records = self.records.lock().unwrap();
+        Ok(find_last_id(&records, channel_id, source))
+    }
+
+    fn get_recent_records(
+        &self,
+        channel_id: u64,
+        source: &str,
+        count: usize,
+    ) -> Result<Vec<ForwardedRecord>, StateError> {
+        let records = self.records.lock().unwrap();
+        Ok(find_recent(&records, channel_id, source, count))
+    }
+
+    fn record_forwarded(&self, record: ForwardedRecord) -> Result<(), StateError> {
+        let mut records = self.records.lock().unwrap();
+        insert_record(&mut records, record);
+        self.save(&records)
+    }
+}
+
+/// Convert `id` to the type SQLite stores integers as. Ids above [i64::MAX] are
+/// stored as negative numbers, so that every id can be read back with [from_sql_id].
+#[cfg(feature = "sqlite")]
+fn to_sql_id(id: u64) -> i64 {
+    id as i64
+}
+
+/// Convert an id stored with [to_sql_id] back to a `u64`.
+#[cfg(feature = "sqlite")]
+fn from_sql_id(id: i64) -> u64 {
+    id as u64
+}
+
+/// Build a record from the columns of a row of the `forwarded` table.
+#[cfg(feature = "sqlite")]
+fn sql_record(
+    channel_id: u64,
+    source: &str,
+    discussion_id: u64,
+    message_ids: &str,
+    edited_at: Option<String>,
+) -> ForwardedRecord {
+    ForwardedRecord {
+        channel_id,
+        source: source.to_string(),
+        discussion_id,
+        message_ids: message_ids
+            .split(',')
+            .filter_map(|id| id.parse().ok())
+            .collect(),
+        edited_at: edited_at
+            .and_then(|time| DateTime::parse_from_rfc3339(&time).ok())
+            .map(|time| time.with_timezone(&Utc)),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    /// Open (creating, if necessary) the database at `path`.
+    pub fn open(path: &Path) -> Result<Self, StateError> {
+        Self::from_connection(rusqlite::Connection::open(path)?)
+    }
+
+    /// Create a store backed by `connection`, creating the store's table if it doesn't exist.
+    pub fn from_connection(connection: rusqlite::Connection) -> Result<Self, StateError> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS forwarded (
+                channel_id INTEGER NOT NULL,
+                source TEXT NOT NULL,
+                discussion_id INTEGER NOT NULL,
+                message_ids TEXT NOT NULL,
+                edited_at TEXT,
+                PRIMARY KEY (channel_id, source, discussion_id)
+            );",
+        )?;
+
+        // Tables created by older versions have no edited_at column.
+        if connection
+            .prepare("SELECT edited_at FROM forwarded LIMIT 0")
+            .is_err()
+        {
+            connection.execute_batch("ALTER TABLE forwarded ADD COLUMN edited_at TEXT;")?;
+        }
+
+        Ok(SqliteStore {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl StateStore for SqliteStore {
+    fn get_record(
+        &self,
+        channel_id: u64,
+        source: &str,
+        discussion_id: u64,
+    ) -> Result<Option<ForwardedRecord>, StateError> {
+        use rusqlite::OptionalExtension;
+
+        let connection = self.connection.lock().unwrap();
+        let row: Option<(String, Option<String>)> = connection
+            .query_row(
+                "SELECT message_ids, edited_at FROM forwarded
+                    WHERE channel_id = ?1 AND source = ?2 AND discussion_id = ?3",
+                rusqlite::params![to_sql_id(channel_id), source, to_sql_id(discussion_id)],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        Ok(row.map(|(message_ids, edited_at)| {
+            sql_record(channel_id, source, discussion_id, &message_ids, edited_at)
+        }))
+    }
+
+    fn get_last_forwarded_id(
+        &self,
+        channel_id: u64,
+        source: &str,
+    ) -> Result<Option<u64>, StateError> {
+        use rusqlite::OptionalExtension;
+
+        // Ids stored as negative numbers are greater than all others.
+        let connection = self.connection.lock().unwrap();
+        let id: Option<i64> = connection
+            .query_row(
+                "SELECT discussion_id FROM forwarded WHERE channel_id = ?1 AND source = ?2
+                    ORDER BY discussion_id < 0 DESC, discussion_id DESC LIMIT 1",
+                rusqlite::params![to_sql_id(channel_id), source],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(id.map(from_sql_id))
+    }
+
+    fn get_recent_records(
+        &self,
+        channel_id: u64,
+        source: &str,
+        count: usize,
+    ) -> Result<Vec<ForwardedRecord>, StateError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT discussion_id, message_ids, edited_at FROM forwarded
+                WHERE channel_id = ?1 AND source = ?2
+                ORDER BY discussion_id < 0 DESC, discussion_id DESC LIMIT ?3",
+        )?;
+        let rows = statement.query_map(
+            rusqlite::params![to_sql_id(channel_id), source, count as i64],
+            |row| {
+                let discussion_id: i64 = row.get(0)?;
+                let message_ids: String = row.get(1)?;
+                let edited_at: Option<String> = row.get(2)?;
+                Ok(sql_record(
+                    channel_id,
+                    source,
+                    from_sql_id(discussion_id),
+                    &message_ids,
+                    edited_at,
+                ))
+            },
+        )?;
+
+        Ok(rows.collect::<Result<Vec<ForwardedRecord>, rusqlite::Error>>()?)
+    }
+
+    fn record_forwarded(&self, record: ForwardedRecord) -> Result<(), StateError> {
+        let message_ids = record
+            .message_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT OR REPLACE INTO forwarded
+                (channel_id, source, discussion_id, message_ids, edited_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                to_sql_id(record.channel_id),
+                record.source,
+                to_sql_id(record.discussion_id),
+                message_ids,
+                record.edited_at.map(|time| time.to_rfc3339())
+            ],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ForwardedRecord, JsonFileStore, MemoryStore, StateStore};
+
+    fn record(channel_id: u64, discussion_id: u64, message_ids: Vec<u64>) -> ForwardedRecord {
+        ForwardedRecord {
+            channel_id,
+            source: "github.com/UWAppDev/community".to_string(),
+            discussion_id,
+            message_ids,
+            edited_at: None,
+        }
+    }
+
+    /// Tests that should pass for every [StateStore].
+    fn check_store(store: &dyn StateStore) {
+        let source = "github.com/UWAppDev/community";
+
+        assert_eq!(store.get_last_forwarded_id(1, source).unwrap(), None);
+        assert!(!store.is_forwarded(1, source, 3).unwrap());
+
+        store.record_forwarded(record(1, 3, vec![100])).unwrap();
+        store
+            .record_forwarded(record(1, 7, vec![101, 102]))
+            .unwrap();
+        store.record_forwarded(record(2, 9, vec![])).unwrap();
+
+        assert!(store.is_forwarded(1, source, 3).unwrap());
+        assert!(!store.is_forwarded(2, source, 3).unwrap());
+        assert!(!store.is_forwarded(1, "github.com/someone/fork", 3).unwrap());
+        assert_eq!(store.get_last_forwarded_id(1, source).unwrap(), Some(7));
+        assert_eq!(store.get_last_forwarded_id(2, source).unwrap(), Some(9));
+        assert_eq!(
+            store.get_record(1, source, 7).unwrap(),
+            Some(record(1, 7, vec![101, 102]))
+        );
+
+        // Ids don't need to fit in 16 bits.
+        store
+            .record_forwarded(record(2, 70_000, vec![104]))
+            .unwrap();
+        assert_eq!(
+            store.get_last_forwarded_id(2, source).unwrap(),
+            Some(70_000)
+        );
+
+        // Ids may use all 64 bits.
+        store
+            .record_forwarded(record(u64::MAX, i64::MAX as u64, vec![]))
+            .unwrap();
+        store
+            .record_forwarded(record(u64::MAX, u64::MAX, vec![u64::MAX]))
+            .unwrap();
+        store.record_forwarded(record(u64::MAX, 5, vec![])).unwrap();
+        assert_eq!(
+            store.get_last_forwarded_id(u64::MAX, source).unwrap(),
+            Some(u64::MAX)
+        );
+        assert_eq!(
+            store.get_record(u64::MAX, source, u64::MAX).unwrap(),
+            Some(record(u64::MAX, u64::MAX, vec![u64::MAX]))
+        );
+
+        assert_eq!(
+            store.get_recent_records(u64::MAX, source, 2).unwrap(),
+            [
+                record(u64::MAX, u64::MAX, vec![u64::MAX]),
+                record(u64::MAX, i64::MAX as u64, vec![])
+            ]
+        );
+
+        // Recording again replaces the old record.
+        let mut edited = record(1, 7, vec![103]);
+        edited.edited_at = Some("2021-09-22T18:04:11Z".parse().unwrap());
+        store.record_forwarded(edited.clone()).unwrap();
+        assert_eq!(
+            store.get_record(1, source, 7).unwrap(),
+            Some(edited.clone())
+        );
+        assert_eq!(
+            store.get_recent_records(1, source, 5).unwrap(),
+            [edited, record(1, 3, vec![100])]
+        );
+        assert!(store.get_recent_records(3, source, 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_memory_store() {
+        check_store(&MemoryStore::new());
+    }
+
+    #[test]
+    fn test_json_store_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        check_store(&JsonFileStore::open(&path).unwrap());
+
+        let reopened = JsonFileStore::open(&path).unwrap();
+        assert_eq!(
+            reopened
+                .get_last_forwarded_id(1, "github.com/UWAppDev/community")
+                .unwrap(),
+            Some(7)
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_persists() {
+        use super::SqliteStore;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.sqlite");
+
+        check_store(&SqliteStore::open(&path).unwrap());
+
+        let reopened = SqliteStore::open(&path).unwrap();
+        assert_eq!(
+            reopened
+                .get_record(1, "github.com/UWAppDev/community", 7)
+                .unwrap()
+                .map(|record| record.message_ids),
+            Some(vec![103])
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_adds_edited_at() {
+        use super::SqliteStore;
+
+        // The table, as created by older versions.
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE forwarded (
+                    channel_id INTEGER NOT NULL,
+                    source TEXT NOT NULL,
+                    discussion_id INTEGER NOT NULL,
+                    message_ids TEXT NOT NULL,
+                    PRIMARY KEY (channel_id, source, discussion_id)
+                );
+                INSERT INTO forwarded VALUES (5, 'github.com/UWAppDev/community', 3, '100');",
+            )
+            .unwrap();
+
+        let store = SqliteStore::from_connection(connection).unwrap();
+        assert_eq!(
+            store
+                .get_record(5, "github.com/UWAppDev/community", 3)
+                .unwrap(),
+            Some(record(5, 3, vec![100]))
+        );
+        check_store(&store);
+    }
+}